@@ -0,0 +1,159 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::{error::Error, js_loop::get_current_time};
+
+/// Callback of one frame of a loop
+pub type Frame = Rc<dyn Fn()>;
+
+/// Time read by the loops once per frame
+pub trait Clock {
+    /// Seconds from an arbitrary origin
+    fn now(&self) -> Result<f64, Error>;
+}
+
+/// Runs the frames of a loop, one request is pending at most
+pub trait Scheduler {
+    /// Calls `frame` once later, returns an id for `cancel`
+    fn request(&self, frame: Frame) -> Result<i32, Error>;
+    fn cancel(&self, id: i32) -> Result<(), Error>;
+}
+
+/// `performance.now()`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BrowserClock;
+
+impl Clock for BrowserClock {
+    fn now(&self) -> Result<f64, Error> {
+        get_current_time()
+    }
+}
+
+/// `requestAnimationFrame`, each loop needs its own
+pub struct BrowserScheduler {
+    closure: Closure<dyn FnMut()>,
+    pending: Rc<RefCell<Option<Frame>>>,
+}
+
+impl BrowserScheduler {
+    pub fn new() -> Self {
+        let pending: Rc<RefCell<Option<Frame>>> = Rc::default();
+        let closure = {
+            let pending = pending.clone();
+            Closure::wrap(Box::new(move || {
+                let frame = pending.borrow_mut().take();
+                if let Some(frame) = frame {
+                    frame();
+                }
+            }) as Box<dyn FnMut()>)
+        };
+        Self { closure, pending }
+    }
+}
+
+impl Default for BrowserScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler for BrowserScheduler {
+    fn request(&self, frame: Frame) -> Result<i32, Error> {
+        *self.pending.borrow_mut() = Some(frame);
+        web_sys::window()
+            .ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?
+            .request_animation_frame(self.closure.as_ref().unchecked_ref())
+            .map_err(Error::js("Request animation frame failed"))
+    }
+
+    fn cancel(&self, id: i32) -> Result<(), Error> {
+        self.pending.borrow_mut().take();
+        web_sys::window()
+            .ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?
+            .cancel_animation_frame(id)
+            .map_err(Error::js("Cancel animation frame failed"))
+    }
+}
+
+/// Clock only moved by hand, to replay or test loops outside of a browser
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<f64>>,
+}
+
+impl ManualClock {
+    pub fn new(seconds: f64) -> Self {
+        Self {
+            now: Rc::new(Cell::new(seconds)),
+        }
+    }
+
+    pub fn set(&self, seconds: f64) {
+        self.now.set(seconds);
+    }
+
+    pub fn advance(&self, seconds: f64) {
+        self.now.set(self.now.get() + seconds);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Result<f64, Error> {
+        Ok(self.now.get())
+    }
+}
+
+/// Runs the requested frame only when `run_frame` is called. Clones share
+/// the pending frame, keep one to drive the loop.
+///
+/// ```ignore
+/// let clock = ManualClock::new(0.);
+/// let scheduler = ManualScheduler::default();
+/// let handle = create_draw_loop_with(clock.clone(), scheduler.clone(), draw)?;
+/// clock.advance(1. / 60.);
+/// assert!(scheduler.run_frame());
+/// ```
+#[derive(Clone, Default)]
+pub struct ManualScheduler {
+    pending: Rc<RefCell<Option<(i32, Frame)>>>,
+    next_id: Rc<Cell<i32>>,
+}
+
+impl ManualScheduler {
+    /// Returns false when no frame was requested, e.g. the loop is paused or stopped
+    pub fn run_frame(&self) -> bool {
+        let frame = self.pending.borrow_mut().take();
+        match frame {
+            Some((_, frame)) => {
+                frame();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.pending.borrow().is_some()
+    }
+}
+
+impl Scheduler for ManualScheduler {
+    fn request(&self, frame: Frame) -> Result<i32, Error> {
+        let id = self.next_id.get() + 1;
+        self.next_id.set(id);
+        *self.pending.borrow_mut() = Some((id, frame));
+        Ok(id)
+    }
+
+    fn cancel(&self, id: i32) -> Result<(), Error> {
+        let mut pending = self.pending.borrow_mut();
+        if matches!(*pending, Some((pending_id, _)) if pending_id == id) {
+            *pending = None;
+        }
+        Ok(())
+    }
+}
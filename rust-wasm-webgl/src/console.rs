@@ -1,11 +1,19 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_namespace = console, js_name = log)]
     pub fn js_log(s: &str);
 }
 
+/// Outside of a browser, e.g. in the tests
+#[cfg(not(target_arch = "wasm32"))]
+pub fn js_log(s: &str) {
+    eprintln!("{}", s);
+}
+
 #[allow(unused_macros)]
 macro_rules! log {
     ($($t:tt)*) => (crate::console::js_log(&format_args!($($t)*).to_string()))
@@ -1,11 +1,138 @@
+use std::fmt;
+
+use wasm_bindgen::{JsCast, JsValue};
+
 use crate::console;
 
-pub fn fmt<E: std::fmt::Debug>(error: E, msg: &str) -> String {
-    format!("{}. Details: {:?}", msg, error)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+/// Errors of the crate. `Display` gives the message and `source` the error it
+/// was caused by, if any. The entry points like `Gl::new` wrap their errors in
+/// `Caused`, match the kind on `root()`:
+///
+/// ```ignore
+/// let gl = match Gl::init() {
+///     Err(error) if matches!(error.root(), Error::Context(_)) => return show_fallback(),
+///     result => result?,
+/// };
+/// ```
+#[derive(Debug)]
+pub enum Error {
+    /// Missing window, document or element, or an element of the wrong type
+    Dom(String),
+    /// WebGL2 unavailable, or a missing capability of the context
+    Context(String),
+    ShaderCompile {
+        stage: ShaderStage,
+        log: String,
+    },
+    ShaderLink(String),
+    /// WebGL object (buffer, texture, framebuffer...) that couldn't be created
+    /// or has no room left
+    Allocation(String),
+    /// Argument or call order rejected before reaching WebGL
+    Invalid(String),
+    Font(String),
+    /// Exception thrown by a JS call
+    Js {
+        message: String,
+        value: JsValue,
+    },
+    /// `source` happened while doing `message`
+    Caused {
+        message: String,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// For `map_err` on JS calls
+    pub fn js(message: &str) -> impl FnOnce(JsValue) -> Error + '_ {
+        move |value| Error::Js {
+            message: String::from(message),
+            value,
+        }
+    }
+
+    /// Wraps the error with what was being done
+    pub fn context(self, message: &str) -> Error {
+        Error::Caused {
+            message: String::from(message),
+            source: Box::new(self),
+        }
+    }
+
+    /// The first error of the chain of `Caused`, the one to match the kind on
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Caused { source, .. } => source.root(),
+            error => error,
+        }
+    }
 }
 
-pub fn report<T>(result: Result<T, String>) {
+impl From<JsValue> for Error {
+    fn from(value: JsValue) -> Self {
+        Error::Js {
+            message: String::from("JS exception"),
+            value,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Dom(message)
+            | Error::Context(message)
+            | Error::Allocation(message)
+            | Error::Invalid(message)
+            | Error::Font(message)
+            | Error::Caused { message, .. } => write!(f, "{}", message),
+            Error::ShaderCompile { stage, log } => {
+                let stage = match stage {
+                    ShaderStage::Vertex => "vertex",
+                    ShaderStage::Fragment => "fragment",
+                };
+                write!(
+                    f,
+                    "Could not compile {} shaders because of:\n{}",
+                    stage, log
+                )
+            }
+            Error::ShaderLink(log) => write!(f, "Couldn't link shaders because of:\n{}", log),
+            Error::Js { message, value } => match value.dyn_ref::<js_sys::Error>() {
+                Some(error) => write!(f, "{}: {}", message, String::from(error.message())),
+                None => match value.as_string() {
+                    Some(text) => write!(f, "{}: {}", message, text),
+                    None => write!(f, "{}: {:?}", message, value),
+                },
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Caused { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+pub fn report<T>(result: Result<T, Error>) {
     if let Err(error) = result {
-        console::log!(" ----- [ERROR] -----\n{}", error);
+        let mut message = error.to_string();
+        let mut source = std::error::Error::source(&error);
+        while let Some(error) = source {
+            message.push_str(&format!("\nCaused by: {}", error));
+            source = error.source();
+        }
+        console::log!(" ----- [ERROR] -----\n{}", message);
     }
-}
\ No newline at end of file
+}
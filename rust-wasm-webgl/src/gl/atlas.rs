@@ -0,0 +1,396 @@
+use web_sys::*;
+
+use super::{
+    context::GlContext,
+    texture::{GlTexture, TextureFormat},
+};
+use crate::error::Error;
+
+/// Bottom-left skyline bin packer
+pub struct SkylinePacker {
+    width: i32,
+    height: i32,
+    skyline: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+impl SkylinePacker {
+    pub fn new(width: i32, height: i32) -> Self {
+        Self {
+            width,
+            height,
+            skyline: vec![Segment { x: 0, y: 0, width }],
+        }
+    }
+
+    /// Returns the top-left corner of the reserved rect or `None` if it doesn't fit
+    pub fn pack(&mut self, width: i32, height: i32) -> Option<(i32, i32)> {
+        let mut best: Option<(usize, i32, i32)> = None;
+
+        for index in 0..self.skyline.len() {
+            if let Some(y) = self.fit(index, width, height) {
+                let x = self.skyline[index].x;
+                if best.is_none_or(|(_, best_x, best_y)| y < best_y || (y == best_y && x < best_x))
+                {
+                    best = Some((index, x, y));
+                }
+            }
+        }
+
+        let (index, x, y) = best?;
+        self.place(index, x, y, width, height);
+        Some((x, y))
+    }
+
+    fn fit(&self, index: usize, width: i32, height: i32) -> Option<i32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut remaining = width;
+        for segment in &self.skyline[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(segment.y);
+            if y + height > self.height {
+                return None;
+            }
+            remaining -= segment.width;
+        }
+        Some(y)
+    }
+
+    fn place(&mut self, index: usize, x: i32, y: i32, width: i32, height: i32) {
+        self.skyline.insert(
+            index,
+            Segment {
+                x,
+                y: y + height,
+                width,
+            },
+        );
+
+        // Shrink or remove the segments now covered by the new one
+        let end = x + width;
+        let next = index + 1;
+        while next < self.skyline.len() {
+            let segment = &mut self.skyline[next];
+            let segment_end = segment.x + segment.width;
+            if segment.x >= end {
+                break;
+            } else if segment_end <= end {
+                self.skyline.remove(next);
+            } else {
+                segment.x = end;
+                segment.width = segment_end - end;
+                break;
+            }
+        }
+
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct AtlasRegion {
+    pub layer: i32,
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+    /// [u min, v min, u max, v max]
+    pub uv: [f32; 4],
+}
+
+enum AtlasImage {
+    Pixels(i32, i32, Vec<u8>),
+    Image(HtmlImageElement),
+}
+
+/// RGBA images packed into the layers (pages) of a texture array.
+/// Images are kept on the cpu so that everything can be repacked
+/// into more pages when a new image doesn't fit.
+pub struct GlAtlas {
+    context: GlContext,
+    page_size: i32,
+    padding: i32,
+    texture: GlTexture,
+    pages: Vec<SkylinePacker>,
+    images: Vec<AtlasImage>,
+    regions: Vec<AtlasRegion>,
+}
+
+impl GlAtlas {
+    pub(super) fn new(context: &GlContext, page_size: i32) -> Result<Self, Error> {
+        Ok(Self {
+            context: context.clone(),
+            page_size,
+            padding: 1,
+            texture: GlTexture::new_2d_array(
+                context,
+                TextureFormat::Rgba8,
+                page_size,
+                page_size,
+                1,
+            )?,
+            pages: vec![SkylinePacker::new(page_size, page_size)],
+            images: vec![],
+            regions: vec![],
+        })
+    }
+
+    /// Adds `width * height` RGBA pixels and returns the index of its region
+    pub fn insert(&mut self, width: i32, height: i32, rgba: Vec<u8>) -> Result<usize, Error> {
+        self.check_size(width, height)?;
+        // Can't overflow once the image fits in a page
+        let expected_len = width as usize * height as usize * 4;
+        if rgba.len() != expected_len {
+            return Err(Error::Invalid(format!(
+                "Expected {} bytes for a {}x{} RGBA image but found {}",
+                expected_len,
+                width,
+                height,
+                rgba.len()
+            )));
+        }
+        self.insert_image(AtlasImage::Pixels(width, height, rgba))
+    }
+
+    pub fn insert_html_image(&mut self, image: &HtmlImageElement) -> Result<usize, Error> {
+        self.insert_image(AtlasImage::Image(image.clone()))
+    }
+
+    pub fn region(&self, index: usize) -> &AtlasRegion {
+        &self.regions[index]
+    }
+
+    pub fn texture(&self) -> &GlTexture {
+        &self.texture
+    }
+
+    pub fn pages_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    fn insert_image(&mut self, image: AtlasImage) -> Result<usize, Error> {
+        let (width, height) = image.size();
+        self.check_size(width, height)?;
+        let padded = (width + 2 * self.padding, height + 2 * self.padding);
+
+        let position = self
+            .pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(layer, page)| Some((layer as i32, page.pack(padded.0, padded.1)?)));
+
+        let index = self.images.len();
+        self.images.push(image);
+
+        match position {
+            Some((layer, (x, y))) => {
+                let region = region_at(self.page_size, self.padding, layer, x, y, width, height);
+                self.regions.push(region);
+                self.upload(index)?;
+            }
+            None => {
+                // `repack` only replaces the regions once every image is packed
+                if let Err(error) = self.repack() {
+                    self.images.truncate(self.regions.len());
+                    return Err(error);
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Packs every image again from scratch, adding pages as needed
+    fn repack(&mut self) -> Result<(), Error> {
+        let sizes: Vec<(i32, i32)> = self.images.iter().map(AtlasImage::size).collect();
+        let (pages, regions) = pack_pages(&sizes, self.page_size, self.padding)?;
+
+        self.texture = GlTexture::new_2d_array(
+            &self.context,
+            TextureFormat::Rgba8,
+            self.page_size,
+            self.page_size,
+            pages.len() as i32,
+        )?;
+        self.pages = pages;
+        self.regions = regions;
+
+        for index in 0..self.images.len() {
+            self.upload(index)?;
+        }
+        Ok(())
+    }
+
+    fn check_size(&self, width: i32, height: i32) -> Result<(), Error> {
+        let max_size = self.page_size - 2 * self.padding;
+        if width <= 0 || height <= 0 || width > max_size || height > max_size {
+            return Err(Error::Invalid(format!(
+                "A {}x{} image doesn't fit in a {}x{} atlas page",
+                width, height, self.page_size, self.page_size
+            )));
+        }
+        Ok(())
+    }
+
+    fn upload(&mut self, index: usize) -> Result<(), Error> {
+        let region = self.regions[index];
+        match &self.images[index] {
+            AtlasImage::Pixels(width, height, rgba) => {
+                self.texture
+                    .upload_region(region.x, region.y, region.layer, *width, *height, rgba)
+            }
+            AtlasImage::Image(image) => {
+                self.texture
+                    .upload_image_region(region.x, region.y, region.layer, image)
+            }
+        }
+    }
+}
+
+/// Packs images of `sizes` tallest first into as many pages as needed,
+/// returns the pages and the regions in the order of `sizes`
+fn pack_pages(
+    sizes: &[(i32, i32)],
+    page_size: i32,
+    padding: i32,
+) -> Result<(Vec<SkylinePacker>, Vec<AtlasRegion>), Error> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| -sizes[i].1);
+
+    let mut pages = vec![SkylinePacker::new(page_size, page_size)];
+    let mut regions = vec![None; sizes.len()];
+
+    for index in order {
+        let (width, height) = sizes[index];
+        let padded = (width + 2 * padding, height + 2 * padding);
+
+        let mut position = pages
+            .iter_mut()
+            .enumerate()
+            .find_map(|(layer, page)| Some((layer as i32, page.pack(padded.0, padded.1)?)));
+
+        if position.is_none() {
+            let mut page = SkylinePacker::new(page_size, page_size);
+            position = page
+                .pack(padded.0, padded.1)
+                .map(|p| (pages.len() as i32, p));
+            pages.push(page);
+        }
+
+        let (layer, (x, y)) =
+            position.ok_or_else(|| Error::Allocation(String::from("Couldn't repack atlas")))?;
+        regions[index] = Some(region_at(page_size, padding, layer, x, y, width, height));
+    }
+    Ok((pages, regions.into_iter().flatten().collect()))
+}
+
+/// Region of an image packed with its `padding` at `x, y`
+fn region_at(
+    page_size: i32,
+    padding: i32,
+    layer: i32,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+) -> AtlasRegion {
+    let x = x + padding;
+    let y = y + padding;
+    let size = page_size as f32;
+    AtlasRegion {
+        layer,
+        x,
+        y,
+        width,
+        height,
+        uv: [
+            x as f32 / size,
+            y as f32 / size,
+            (x + width) as f32 / size,
+            (y + height) as f32 / size,
+        ],
+    }
+}
+
+impl AtlasImage {
+    fn size(&self) -> (i32, i32) {
+        match self {
+            AtlasImage::Pixels(width, height, _) => (*width, *height),
+            AtlasImage::Image(image) => {
+                (image.natural_width() as i32, image.natural_height() as i32)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_at_the_lowest_then_leftmost_spot() {
+        let mut packer = SkylinePacker::new(10, 10);
+        assert_eq!(packer.pack(4, 4), Some((0, 0)));
+        assert_eq!(packer.pack(4, 2), Some((4, 0)));
+        assert_eq!(packer.pack(2, 3), Some((8, 0)));
+        assert_eq!(packer.pack(4, 4), Some((4, 2)));
+    }
+
+    #[test]
+    fn merges_segments_of_the_same_height() {
+        let mut packer = SkylinePacker::new(10, 10);
+        packer.pack(5, 3);
+        packer.pack(5, 3);
+        assert_eq!(packer.skyline.len(), 1);
+        let segment = packer.skyline[0];
+        assert_eq!((segment.x, segment.y, segment.width), (0, 3, 10));
+    }
+
+    #[test]
+    fn fails_when_the_page_is_full() {
+        let mut packer = SkylinePacker::new(10, 10);
+        assert_eq!(packer.pack(11, 1), None);
+        assert_eq!(packer.pack(10, 10), Some((0, 0)));
+        assert_eq!(packer.pack(1, 1), None);
+    }
+
+    #[test]
+    fn regions_are_inside_the_padding() {
+        let (pages, regions) = pack_pages(&[(4, 4), (6, 6)], 16, 1).unwrap();
+        assert_eq!(pages.len(), 1);
+        // Tallest first, each image is 2 pixels larger with its padding
+        assert_eq!((regions[1].x, regions[1].y), (1, 1));
+        assert_eq!((regions[0].x, regions[0].y), (9, 1));
+        assert_eq!(regions[0].uv, [9. / 16., 1. / 16., 13. / 16., 5. / 16.]);
+    }
+
+    #[test]
+    fn repacking_adds_pages() {
+        let (pages, regions) = pack_pages(&[(14, 14); 3], 16, 1).unwrap();
+        assert_eq!(pages.len(), 3);
+        let layers: Vec<i32> = regions.iter().map(|region| region.layer).collect();
+        assert_eq!(layers, [0, 1, 2]);
+        assert!(pack_pages(&[(15, 15)], 16, 1).is_err());
+    }
+}
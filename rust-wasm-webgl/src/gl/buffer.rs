@@ -1,39 +1,74 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
 use web_sys::*;
 
+use super::context::GlContext;
+use crate::error::Error;
+
 pub struct GlBuffer {
-    context: WebGl2RenderingContext,
-    buffer: WebGlBuffer,
-    len: usize,
+    pub(super) shared: Rc<SharedBuffer>,
 }
 
-const TARGET: u32 = WebGl2RenderingContext::ARRAY_BUFFER;
+/// State of the buffer kept by `GlContext` to restore it
+pub(super) struct SharedBuffer {
+    context: GlContext,
+    buffer: RefCell<WebGlBuffer>,
+    target: u32,
+    len: Cell<usize>,
+    /// Kept to upload it again after a context loss
+    static_data: RefCell<Option<Vec<f32>>>,
+}
 
 impl GlBuffer {
-    pub fn new(gl_context: &WebGl2RenderingContext) -> Result<GlBuffer, String> {
-        Ok(GlBuffer {
-            context: gl_context.clone(),
-            buffer: gl_context
-                .create_buffer()
-                .ok_or("Unable to create gl buffer")?,
-            len: 0,
-        })
+    pub(super) fn new(gl_context: &GlContext) -> Result<GlBuffer, Error> {
+        Self::with_target(gl_context, WebGl2RenderingContext::ARRAY_BUFFER)
+    }
+
+    /// Buffer for uniform blocks, see `bind_base`
+    pub(super) fn new_uniform(gl_context: &GlContext) -> Result<GlBuffer, Error> {
+        Self::with_target(gl_context, WebGl2RenderingContext::UNIFORM_BUFFER)
     }
 
-    pub fn new_static(
-        gl_context: &WebGl2RenderingContext,
-        data: &[f32],
-    ) -> Result<GlBuffer, String> {
+    fn with_target(gl_context: &GlContext, target: u32) -> Result<GlBuffer, Error> {
+        let buffer = GlBuffer {
+            shared: Rc::new(SharedBuffer {
+                context: gl_context.clone(),
+                buffer: RefCell::new(create_buffer(gl_context)?),
+                target,
+                len: Cell::new(0),
+                static_data: RefCell::new(None),
+            }),
+        };
+        gl_context.register(&buffer.shared, |shared| GlBuffer { shared }.restore());
+        Ok(buffer)
+    }
+
+    pub(super) fn new_static(gl_context: &GlContext, data: &[f32]) -> Result<GlBuffer, Error> {
         let mut buffer = Self::new(gl_context)?;
         buffer.allocate_static(data);
         Ok(buffer)
     }
 
     pub fn bind(&self) {
-        self.context.bind_buffer(TARGET, Some(&self.buffer));
+        let shared = &self.shared;
+        shared
+            .context
+            .bind_buffer(shared.target, Some(&shared.buffer.borrow()));
+    }
+
+    /// Binds a uniform buffer to the uniform block binding point `index`
+    pub fn bind_base(&self, index: u32) {
+        let shared = &self.shared;
+        shared
+            .context
+            .bind_buffer_base(shared.target, index, Some(&shared.buffer.borrow()));
     }
 
     pub fn update(&mut self, data: &[f32]) {
-        if self.len < data.len() {
+        if self.shared.len.get() < data.len() {
             self.allocate_data(data, WebGl2RenderingContext::DYNAMIC_DRAW);
         } else {
             self.update_slice(data, 0);
@@ -42,9 +77,22 @@ impl GlBuffer {
 
     /// faster than allocate if you don't want to update data frequently
     pub fn allocate_static(&mut self, data: &[f32]) {
+        *self.shared.static_data.borrow_mut() = Some(data.to_vec());
         self.allocate_data(data, WebGl2RenderingContext::STATIC_DRAW);
     }
 
+    /// Recreates the buffer after the context was restored. Static data is uploaded
+    /// again, dynamic buffers are empty until their next `update`.
+    fn restore(mut self) -> Result<(), Error> {
+        *self.shared.buffer.borrow_mut() = create_buffer(&self.shared.context)?;
+        self.shared.len.set(0);
+        let static_data = self.shared.static_data.borrow_mut().take();
+        if let Some(data) = static_data {
+            self.allocate_static(&data);
+        }
+        Ok(())
+    }
+
     pub fn update_slice(&self, data: &[f32], dst_byte_offset: i32) {
         self.bind();
 
@@ -59,17 +107,19 @@ impl GlBuffer {
         unsafe {
             let array_buffer_view = js_sys::Float32Array::view(&data);
 
-            self.context.buffer_sub_data_with_i32_and_array_buffer_view(
-                WebGl2RenderingContext::ARRAY_BUFFER,
-                dst_byte_offset,
-                &array_buffer_view,
-            );
+            self.shared
+                .context
+                .buffer_sub_data_with_i32_and_array_buffer_view(
+                    self.shared.target,
+                    dst_byte_offset,
+                    &array_buffer_view,
+                );
         }
     }
 
     fn allocate_data(&mut self, data: &[f32], usage: u32) {
         self.bind();
-        self.len = data.len();
+        self.shared.len.set(data.len());
 
         // Note that `Float32Array::view` is somewhat dangerous (hence the
         // `unsafe`!). This is creating a raw view into our module's
@@ -82,8 +132,8 @@ impl GlBuffer {
         unsafe {
             let array_buffer_view = js_sys::Float32Array::view(&data);
 
-            self.context.buffer_data_with_array_buffer_view(
-                WebGl2RenderingContext::ARRAY_BUFFER,
+            self.shared.context.buffer_data_with_array_buffer_view(
+                self.shared.target,
                 &array_buffer_view,
                 usage,
             );
@@ -91,8 +141,14 @@ impl GlBuffer {
     }
 }
 
-impl Drop for GlBuffer {
+fn create_buffer(context: &WebGl2RenderingContext) -> Result<WebGlBuffer, Error> {
+    context
+        .create_buffer()
+        .ok_or_else(|| Error::Allocation(String::from("Unable to create gl buffer")))
+}
+
+impl Drop for SharedBuffer {
     fn drop(&mut self) {
-        self.context.delete_buffer(Some(&self.buffer));
+        self.context.delete_buffer(Some(&self.buffer.borrow()));
     }
 } 
\ No newline at end of file
@@ -0,0 +1,394 @@
+use super::math::*;
+
+/// With `zoom` 1 the view spans from -1 to 1 vertically, and
+/// horizontally as much as the viewport aspect ratio allows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    pub center: [f32; 2],
+    pub zoom: f32,
+    /// Radians, counterclockwise
+    pub rotation: f32,
+}
+
+impl Default for Camera2D {
+    fn default() -> Self {
+        Self {
+            center: [0., 0.],
+            zoom: 1.,
+            rotation: 0.,
+        }
+    }
+}
+
+impl Camera2D {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `viewport` is the size in pixels of the surface the camera renders to
+    pub fn view_projection(&self, viewport: (i32, i32)) -> Mat4 {
+        let (sx, sy) = self.scale(viewport);
+        let (sin, cos) = self.rotation.sin_cos();
+        let [cx, cy] = self.center;
+
+        [
+            sx * cos,
+            -sy * sin,
+            0.,
+            0.,
+            sx * sin,
+            sy * cos,
+            0.,
+            0.,
+            0.,
+            0.,
+            1.,
+            0.,
+            -sx * (cos * cx + sin * cy),
+            -sy * (-sin * cx + cos * cy),
+            0.,
+            1.,
+        ]
+    }
+
+    /// `screen` is in pixels from the top left corner of the viewport
+    pub fn screen_to_world(&self, screen: [f32; 2], viewport: (i32, i32)) -> [f32; 2] {
+        let (sx, sy) = self.scale(viewport);
+        let clip_x = screen[0] / viewport.0 as f32 * 2. - 1.;
+        let clip_y = 1. - screen[1] / viewport.1 as f32 * 2.;
+
+        let (x, y) = (clip_x / sx, clip_y / sy);
+        let (sin, cos) = self.rotation.sin_cos();
+        [
+            cos * x - sin * y + self.center[0],
+            sin * x + cos * y + self.center[1],
+        ]
+    }
+
+    pub fn world_to_screen(&self, world: [f32; 2], viewport: (i32, i32)) -> [f32; 2] {
+        let (sx, sy) = self.scale(viewport);
+        let (sin, cos) = self.rotation.sin_cos();
+        let (x, y) = (world[0] - self.center[0], world[1] - self.center[1]);
+
+        let clip_x = (cos * x + sin * y) * sx;
+        let clip_y = (-sin * x + cos * y) * sy;
+        [
+            (clip_x + 1.) / 2. * viewport.0 as f32,
+            (1. - clip_y) / 2. * viewport.1 as f32,
+        ]
+    }
+
+    /// Moves the camera so the world follows a drag of `delta` screen pixels
+    pub fn pan(&mut self, delta: [f32; 2], viewport: (i32, i32)) {
+        let origin = self.screen_to_world([0., 0.], viewport);
+        let moved = self.screen_to_world(delta, viewport);
+        self.center[0] -= moved[0] - origin[0];
+        self.center[1] -= moved[1] - origin[1];
+    }
+
+    /// Multiplies the zoom keeping the world point under `screen` in place
+    pub fn zoom_at(&mut self, screen: [f32; 2], factor: f32, viewport: (i32, i32)) {
+        let before = self.screen_to_world(screen, viewport);
+        self.zoom *= factor;
+        let after = self.screen_to_world(screen, viewport);
+        self.center[0] += before[0] - after[0];
+        self.center[1] += before[1] - after[1];
+    }
+
+    fn scale(&self, viewport: (i32, i32)) -> (f32, f32) {
+        let aspect = viewport.0.max(1) as f32 / viewport.1.max(1) as f32;
+        (self.zoom / aspect, self.zoom)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelResolution {
+    /// One unit is one CSS pixel
+    Css,
+    /// One unit is one canvas pixel
+    Device,
+    /// Fixed resolution scaled by the largest integer factor that fits the canvas,
+    /// centered with bars around it
+    Virtual(i32, i32),
+}
+
+/// Coordinates in pixels with the origin at the top left corner
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelCamera {
+    pub resolution: PixelResolution,
+    /// Round vertices to the device pixel grid, so quad edges stay sharp
+    pub snap: bool,
+}
+
+impl Default for PixelCamera {
+    fn default() -> Self {
+        Self {
+            resolution: PixelResolution::Css,
+            snap: true,
+        }
+    }
+}
+
+/// Where a `PixelCamera` draws on a canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PixelLayout {
+    /// Visible size in units
+    pub size: [f32; 2],
+    /// `x, y, width, height` in canvas pixels from the bottom left corner, as `gl.viewport`
+    pub viewport: [i32; 4],
+    /// Canvas pixels per unit
+    pub scale: f32,
+}
+
+impl PixelCamera {
+    pub fn new(resolution: PixelResolution) -> Self {
+        Self {
+            resolution,
+            ..Self::default()
+        }
+    }
+
+    /// `css_ratio` is the number of canvas pixels per CSS pixel
+    pub fn layout(&self, canvas_size: (i32, i32), css_ratio: f32) -> PixelLayout {
+        let (width, height) = (canvas_size.0.max(1), canvas_size.1.max(1));
+        let full = [0, 0, width, height];
+        match self.resolution {
+            PixelResolution::Css => PixelLayout {
+                size: [width as f32 / css_ratio, height as f32 / css_ratio],
+                viewport: full,
+                scale: css_ratio,
+            },
+            PixelResolution::Device => PixelLayout {
+                size: [width as f32, height as f32],
+                viewport: full,
+                scale: 1.,
+            },
+            PixelResolution::Virtual(virtual_width, virtual_height) => {
+                let (virtual_width, virtual_height) = (virtual_width.max(1), virtual_height.max(1));
+                let scale = (width / virtual_width).min(height / virtual_height).max(1);
+                let (viewport_width, viewport_height) =
+                    (virtual_width * scale, virtual_height * scale);
+                PixelLayout {
+                    size: [virtual_width as f32, virtual_height as f32],
+                    viewport: [
+                        (width - viewport_width) / 2,
+                        (height - viewport_height) / 2,
+                        viewport_width,
+                        viewport_height,
+                    ],
+                    scale: scale as f32,
+                }
+            }
+        }
+    }
+
+    /// Maps the layout `size` with y pointing down, to be used with the layout viewport
+    pub fn view_projection(&self, layout: &PixelLayout) -> Mat4 {
+        let [width, height] = layout.size;
+        [
+            2. / width,
+            0.,
+            0.,
+            0.,
+            0.,
+            -2. / height,
+            0.,
+            0.,
+            0.,
+            0.,
+            1.,
+            0.,
+            -1.,
+            1.,
+            0.,
+            1.,
+        ]
+    }
+
+    /// `screen` is in canvas pixels from its top left corner
+    pub fn screen_to_world(
+        &self,
+        screen: [f32; 2],
+        layout: &PixelLayout,
+        canvas_size: (i32, i32),
+    ) -> [f32; 2] {
+        let [x, y, _, height] = layout.viewport;
+        let top = canvas_size.1 - y - height;
+        [
+            (screen[0] - x as f32) / layout.scale,
+            (screen[1] - top as f32) / layout.scale,
+        ]
+    }
+
+    pub fn world_to_screen(
+        &self,
+        world: [f32; 2],
+        layout: &PixelLayout,
+        canvas_size: (i32, i32),
+    ) -> [f32; 2] {
+        let [x, y, _, height] = layout.viewport;
+        let top = canvas_size.1 - y - height;
+        [
+            world[0] * layout.scale + x as f32,
+            world[1] * layout.scale + top as f32,
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective {
+        /// Vertical field of view in radians
+        fov_y: f32,
+        near: f32,
+        far: f32,
+    },
+    Orthographic {
+        /// Visible height in world units
+        height: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera3D {
+    pub eye: [f32; 3],
+    pub target: [f32; 3],
+    pub up: [f32; 3],
+    pub projection: Projection,
+}
+
+impl Default for Camera3D {
+    fn default() -> Self {
+        Self {
+            eye: [0., 0., 5.],
+            target: [0., 0., 0.],
+            up: [0., 1., 0.],
+            projection: Projection::Perspective {
+                fov_y: std::f32::consts::FRAC_PI_4,
+                near: 0.1,
+                far: 100.,
+            },
+        }
+    }
+}
+
+impl Camera3D {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn look_at(&mut self, eye: [f32; 3], target: [f32; 3]) -> &mut Self {
+        self.eye = eye;
+        self.target = target;
+        self
+    }
+
+    pub fn view(&self) -> Mat4 {
+        mat4_look_at(self.eye, self.target, self.up)
+    }
+
+    pub fn projection(&self, viewport: (i32, i32)) -> Mat4 {
+        let aspect = viewport.0.max(1) as f32 / viewport.1.max(1) as f32;
+        match self.projection {
+            Projection::Perspective { fov_y, near, far } => {
+                mat4_perspective(fov_y, aspect, near, far)
+            }
+            Projection::Orthographic { height, near, far } => {
+                let half_height = height / 2.;
+                let half_width = half_height * aspect;
+                mat4_orthographic(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+        }
+    }
+
+    pub fn view_projection(&self, viewport: (i32, i32)) -> Mat4 {
+        mat4_mul(&self.projection(viewport), &self.view())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (value[0] - expected[0]).abs() < 1e-3 && (value[1] - expected[1]).abs() < 1e-3,
+            "expected {:?} but found {:?}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn screen_and_world_positions_round_trip() {
+        let camera = Camera2D {
+            center: [3., -2.],
+            zoom: 0.5,
+            rotation: 0.7,
+        };
+        let viewport = (800, 600);
+        for screen in [[0., 0.], [400., 300.], [123., 456.], [800., 600.]] {
+            let world = camera.screen_to_world(screen, viewport);
+            assert_close(camera.world_to_screen(world, viewport), screen);
+        }
+        assert_close(camera.world_to_screen([3., -2.], viewport), [400., 300.]);
+    }
+
+    #[test]
+    fn screen_is_y_down_and_spans_the_zoomed_height() {
+        let camera = Camera2D::new();
+        let viewport = (400, 200);
+        assert_close(camera.screen_to_world([200., 0.], viewport), [0., 1.]);
+        assert_close(camera.screen_to_world([400., 200.], viewport), [2., -1.]);
+    }
+
+    #[test]
+    fn zoom_keeps_the_anchor_in_place() {
+        let mut camera = Camera2D {
+            rotation: 0.3,
+            ..Camera2D::new()
+        };
+        let (viewport, anchor) = ((640, 480), [100., 50.]);
+        let world = camera.screen_to_world(anchor, viewport);
+        camera.zoom_at(anchor, 2.5, viewport);
+        assert_eq!(camera.zoom, 2.5);
+        assert_close(camera.world_to_screen(world, viewport), anchor);
+        assert_close(camera.screen_to_world(anchor, viewport), world);
+    }
+
+    #[test]
+    fn virtual_resolution_scales_by_an_exact_multiple() {
+        let camera = PixelCamera::new(PixelResolution::Virtual(320, 180));
+        let layout = camera.layout((1280, 720), 2.);
+        assert_eq!(layout.viewport, [0, 0, 1280, 720]);
+        assert_eq!(layout.scale, 4.);
+        assert_eq!(layout.size, [320., 180.]);
+    }
+
+    #[test]
+    fn virtual_resolution_centers_the_bars() {
+        let camera = PixelCamera::new(PixelResolution::Virtual(320, 180));
+        let layout = camera.layout((1000, 700), 1.);
+        assert_eq!(layout.scale, 3.);
+        assert_eq!(layout.viewport, [20, 80, 960, 540]);
+        assert_eq!(layout.size, [320., 180.]);
+    }
+
+    #[test]
+    fn virtual_resolution_keeps_a_scale_of_one_on_a_small_canvas() {
+        let camera = PixelCamera::new(PixelResolution::Virtual(320, 180));
+        let layout = camera.layout((200, 100), 1.);
+        assert_eq!(layout.scale, 1.);
+        assert_eq!(layout.viewport, [-60, -40, 320, 180]);
+        assert_eq!(layout.size, [320., 180.]);
+    }
+}
@@ -0,0 +1,146 @@
+use wasm_bindgen::JsCast;
+use web_sys::*;
+
+use crate::error::Error;
+
+pub enum CanvasTarget {
+    /// Id of a canvas element in the document
+    Id(String),
+    Element(HtmlCanvasElement),
+    /// Only resized with `Gl::set_size`, can be used from a worker
+    Offscreen(OffscreenCanvas),
+}
+
+/// Attributes of the WebGL context, only used on its creation
+#[derive(Debug, Clone, Copy)]
+pub struct ContextOptions {
+    pub antialias: bool,
+    pub alpha: bool,
+    pub premultiplied_alpha: bool,
+    /// Keeps the frame after it's shown, needed to read it back later
+    pub preserve_drawing_buffer: bool,
+    pub power_preference: WebGlPowerPreference,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self {
+            antialias: true,
+            alpha: true,
+            premultiplied_alpha: true,
+            preserve_drawing_buffer: false,
+            power_preference: WebGlPowerPreference::Default,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanvasResize {
+    /// Fill the browser window
+    Window,
+    /// Follow the size given to the canvas element by CSS. The canvas needs
+    /// a CSS width and height, without them its size would follow the drawing
+    /// buffer and `Gl::update_size` fails.
+    Css,
+    /// Only resized with `Gl::set_size`
+    Manual,
+}
+
+#[derive(Clone)]
+pub(super) enum Canvas {
+    Element(HtmlCanvasElement),
+    Offscreen(OffscreenCanvas),
+}
+
+impl Canvas {
+    pub(super) fn load(target: CanvasTarget) -> Result<Canvas, Error> {
+        Ok(match target {
+            CanvasTarget::Id(id) => {
+                let window = web_sys::window()
+                    .ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?;
+                let document = window
+                    .document()
+                    .ok_or_else(|| Error::Dom(String::from("Couldn't get document")))?;
+
+                let canvas = document
+                    .get_element_by_id(&id)
+                    .ok_or_else(|| Error::Dom(format!("Couldn't get canvas #{}", id)))?;
+                Canvas::Element(
+                    canvas
+                        .dyn_into::<HtmlCanvasElement>()
+                        .map_err(|_| Error::Dom(String::from("Invalid canvas")))?,
+                )
+            }
+            CanvasTarget::Element(canvas) => Canvas::Element(canvas),
+            CanvasTarget::Offscreen(canvas) => Canvas::Offscreen(canvas),
+        })
+    }
+
+    pub(super) fn context(
+        &self,
+        options: &ContextOptions,
+    ) -> Result<WebGl2RenderingContext, Error> {
+        let mut attributes = WebGlContextAttributes::new();
+        attributes
+            .antialias(options.antialias)
+            .alpha(options.alpha)
+            .premultiplied_alpha(options.premultiplied_alpha)
+            .preserve_drawing_buffer(options.preserve_drawing_buffer)
+            .power_preference(options.power_preference);
+
+        let context = match self {
+            Canvas::Element(canvas) => {
+                canvas.get_context_with_context_options("webgl2", &attributes)
+            }
+            Canvas::Offscreen(canvas) => {
+                canvas.get_context_with_context_options("webgl2", &attributes)
+            }
+        };
+        context
+            .map_err(Error::js("Couldn't get webgl2 context"))?
+            .ok_or_else(|| Error::Context(String::from("WebGL2 is not supported")))?
+            .dyn_into::<WebGl2RenderingContext>()
+            .map_err(|_| Error::Context(String::from("Invalid webgl2 context")))
+    }
+
+    pub(super) fn element(&self) -> Option<&HtmlCanvasElement> {
+        match self {
+            Canvas::Element(canvas) => Some(canvas),
+            Canvas::Offscreen(_) => None,
+        }
+    }
+
+    pub(super) fn event_target(&self) -> &EventTarget {
+        match self {
+            Canvas::Element(canvas) => canvas,
+            Canvas::Offscreen(canvas) => canvas,
+        }
+    }
+
+    pub(super) fn width(&self) -> u32 {
+        match self {
+            Canvas::Element(canvas) => canvas.width(),
+            Canvas::Offscreen(canvas) => canvas.width(),
+        }
+    }
+
+    pub(super) fn height(&self) -> u32 {
+        match self {
+            Canvas::Element(canvas) => canvas.height(),
+            Canvas::Offscreen(canvas) => canvas.height(),
+        }
+    }
+
+    pub(super) fn set_size(&self, width: u32, height: u32) {
+        match self {
+            Canvas::Element(canvas) => {
+                canvas.set_width(width);
+                canvas.set_height(height);
+            }
+            Canvas::Offscreen(canvas) => {
+                canvas.set_width(width);
+                canvas.set_height(height);
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use wasm_bindgen::{prelude::*, JsCast};
+
+use super::*;
+use crate::{
+    clock::{Clock, ManualClock},
+    png::encode_png,
+};
+
+/// Pixels read back from the canvas, rows from top to bottom
+pub struct Capture {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl Capture {
+    pub(super) fn read(gl: &Gl) -> Result<Self, Error> {
+        let (width, height) = gl.canvas_size();
+        let row_len = width as usize * 4;
+        let mut pixels = vec![0; row_len * height as usize];
+
+        gl.context
+            .bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        gl.context
+            .pixel_storei(WebGl2RenderingContext::PACK_ALIGNMENT, 1);
+        let result = gl.context.read_pixels_with_opt_u8_array(
+            0,
+            0,
+            width,
+            height,
+            WebGl2RenderingContext::RGBA,
+            WebGl2RenderingContext::UNSIGNED_BYTE,
+            Some(&mut pixels),
+        );
+        gl.bind_canvas();
+        result.map_err(Error::js("Couldn't read canvas pixels"))?;
+
+        // WebGL reads from the bottom row
+        let rgba = pixels
+            .chunks_exact(row_len.max(1))
+            .rev()
+            .flatten()
+            .copied()
+            .collect();
+        Ok(Self {
+            width: width as u32,
+            height: height as u32,
+            rgba,
+        })
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, Error> {
+        encode_png(self.width, self.height, &self.rgba)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RecordOptions {
+    /// Captures one frame out of `every`
+    pub every: u32,
+    /// Seconds of virtual time between two frames
+    pub step: f64,
+    /// Stops capturing after this many images
+    pub frames: Option<u32>,
+}
+
+impl Default for RecordOptions {
+    fn default() -> Self {
+        Self {
+            every: 1,
+            step: 1. / 60.,
+            frames: None,
+        }
+    }
+}
+
+/// Captures an image sequence of a loop running on virtual time, so every
+/// recording of the same animation gives the same images however slow the
+/// capture is.
+///
+/// ```ignore
+/// let mut recorder = Recorder::new(RecordOptions::default());
+/// create_draw_loop_with(recorder.clock(), BrowserScheduler::new(), move |time| {
+///     // draw...
+///     gl.present()?;
+///     if let Some((index, png)) = recorder.end_frame(&gl)? {
+///         download(&png, &format!("frame-{:04}.png", index), "image/png")?;
+///     }
+///     Ok(())
+/// })?;
+/// ```
+pub struct Recorder {
+    options: RecordOptions,
+    clock: ManualClock,
+    frame_count: u32,
+    captured: u32,
+}
+
+impl Recorder {
+    pub fn new(options: RecordOptions) -> Self {
+        Self {
+            options: RecordOptions {
+                every: options.every.max(1),
+                ..options
+            },
+            clock: ManualClock::new(0.),
+            frame_count: 0,
+            captured: 0,
+        }
+    }
+
+    /// Clock of the recorded loop, advanced by `step` on each `end_frame`
+    pub fn clock(&self) -> RecordClock {
+        RecordClock {
+            clock: self.clock.clone(),
+            step: self.options.step,
+            started: Rc::default(),
+        }
+    }
+
+    /// Call after `Gl::present`. Returns the index and PNG of the captured frames.
+    pub fn end_frame(&mut self, gl: &Gl) -> Result<Option<(u32, Vec<u8>)>, Error> {
+        let frame = self.frame_count;
+        self.frame_count += 1;
+        self.clock.advance(self.options.step);
+
+        if self.is_done() || !frame.is_multiple_of(self.options.every) {
+            return Ok(None);
+        }
+        let png = gl.capture()?.to_png()?;
+        self.captured += 1;
+        Ok(Some((self.captured - 1, png)))
+    }
+
+    pub fn captured(&self) -> u32 {
+        self.captured
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.options
+            .frames
+            .is_some_and(|frames| self.captured >= frames)
+    }
+}
+
+/// Clock of `Recorder`. Its first read, when the loop is created, is one step
+/// before the first frame so that frame doesn't have a delta of 0.
+#[derive(Clone)]
+pub struct RecordClock {
+    clock: ManualClock,
+    step: f64,
+    started: Rc<Cell<bool>>,
+}
+
+impl Clock for RecordClock {
+    fn now(&self) -> Result<f64, Error> {
+        let now = self.clock.now()?;
+        if self.started.replace(true) {
+            Ok(now)
+        } else {
+            Ok(now - self.step)
+        }
+    }
+}
+
+/// Makes the browser download `bytes` as `file_name`
+pub fn download(bytes: &[u8], file_name: &str, mime_type: &str) -> Result<(), Error> {
+    let window =
+        web_sys::window().ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?;
+    let document = window
+        .document()
+        .ok_or_else(|| Error::Dom(String::from("Couldn't get document")))?;
+
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let mut options = BlobPropertyBag::new();
+    options.type_(mime_type);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .map_err(Error::js("Couldn't create blob"))?;
+    let url =
+        Url::create_object_url_with_blob(&blob).map_err(Error::js("Couldn't create object url"))?;
+
+    let link = document
+        .create_element("a")
+        .map_err(Error::js("Couldn't create link"))?
+        .dyn_into::<HtmlAnchorElement>()
+        .map_err(|_| Error::Dom(String::from("Invalid link")))?;
+    link.set_href(&url);
+    link.set_download(file_name);
+    link.click();
+
+    // Revoked later, the download may not have started yet
+    let revoke = Closure::once_into_js(move || Url::revoke_object_url(&url));
+    window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(revoke.unchecked_ref(), 10_000)
+        .map_err(Error::js("Couldn't schedule the object url revocation"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    use crate::{
+        clock::ManualScheduler,
+        js_loop::{create_draw_loop_with, FrameTime},
+    };
+
+    #[test]
+    fn recorded_frames_are_one_step_apart() {
+        let recorder = Recorder::new(RecordOptions {
+            step: 0.25,
+            ..RecordOptions::default()
+        });
+        let scheduler = ManualScheduler::default();
+        let times: Rc<RefCell<Vec<FrameTime>>> = Rc::default();
+        let frame_times = times.clone();
+        let _handle = create_draw_loop_with(recorder.clock(), scheduler.clone(), move |time| {
+            frame_times.borrow_mut().push(time);
+            Ok(())
+        })
+        .unwrap();
+
+        for _ in 0..2 {
+            assert!(scheduler.run_frame());
+            // As `end_frame` does
+            recorder.clock.advance(0.25);
+        }
+        let times = times.borrow();
+        assert_eq!([times[0].seconds, times[1].seconds], [0., 0.25]);
+        assert_eq!([times[0].delta, times[1].delta], [0.25, 0.25]);
+    }
+}
@@ -0,0 +1,203 @@
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    ops::Deref,
+    rc::{Rc, Weak},
+};
+
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::*;
+
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextStatus {
+    Ready,
+    /// Nothing can be drawn until the browser restores the context
+    Lost,
+    /// The context was just restored along with the resources created from `Gl`
+    Restored,
+}
+
+/// The WebGL context and the objects created from it, which are recreated by
+/// `restore_resources` after a context loss
+#[derive(Clone)]
+pub(super) struct GlContext {
+    context: WebGl2RenderingContext,
+    resources: Rc<RefCell<Resources>>,
+}
+
+#[derive(Default)]
+struct Resources {
+    /// In creation order, so objects are restored after the ones they depend on
+    objects: Vec<Resource>,
+    /// Run once every object is restored, e.g. to link buffers to vaos again
+    links: Vec<Resource>,
+}
+
+struct Resource {
+    shared: Weak<dyn Any>,
+    restore: Box<dyn Fn() -> Result<(), Error>>,
+}
+
+impl GlContext {
+    pub(super) fn new(context: WebGl2RenderingContext) -> Self {
+        Self {
+            context,
+            resources: Rc::default(),
+        }
+    }
+
+    /// `restore` recreates the object from its `shared` state, until the state is dropped
+    pub(super) fn register<T: 'static>(
+        &self,
+        shared: &Rc<T>,
+        restore: impl Fn(Rc<T>) -> Result<(), Error> + 'static,
+    ) {
+        let mut resources = self.resources.borrow_mut();
+        resources.objects.retain(Resource::is_alive);
+        resources.objects.push(Resource::new(shared, restore));
+    }
+
+    /// Same as `register`, `link` runs after every object was restored
+    pub(super) fn register_link<T: 'static>(
+        &self,
+        shared: &Rc<T>,
+        link: impl Fn(Rc<T>) -> Result<(), Error> + 'static,
+    ) {
+        let mut resources = self.resources.borrow_mut();
+        resources.links.retain(Resource::is_alive);
+        resources.links.push(Resource::new(shared, link));
+    }
+
+    pub(super) fn restore_resources(&self) -> Result<(), Error> {
+        self.run(|resources| &mut resources.objects)?;
+        self.run(|resources| &mut resources.links)
+    }
+
+    /// The list is taken out while running, restoring an object can create new ones
+    fn run(&self, list: fn(&mut Resources) -> &mut Vec<Resource>) -> Result<(), Error> {
+        let resources = std::mem::take(list(&mut self.resources.borrow_mut()));
+        let mut result = Ok(());
+        let mut kept = Vec::with_capacity(resources.len());
+        for resource in resources.into_iter().filter(Resource::is_alive) {
+            if result.is_ok() {
+                result = (resource.restore)();
+            }
+            kept.push(resource);
+        }
+
+        let mut all = self.resources.borrow_mut();
+        let list = list(&mut all);
+        kept.append(list);
+        *list = kept;
+        result
+    }
+}
+
+impl Deref for GlContext {
+    type Target = WebGl2RenderingContext;
+
+    fn deref(&self) -> &WebGl2RenderingContext {
+        &self.context
+    }
+}
+
+impl Resource {
+    fn new<T: 'static>(
+        shared: &Rc<T>,
+        restore: impl Fn(Rc<T>) -> Result<(), Error> + 'static,
+    ) -> Self {
+        let weak = Rc::downgrade(shared);
+        Self {
+            shared: weak.clone(),
+            restore: Box::new(move || match weak.upgrade() {
+                Some(shared) => restore(shared),
+                None => Ok(()),
+            }),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        self.shared.strong_count() > 0
+    }
+}
+
+type Callbacks = Rc<RefCell<Vec<Box<dyn FnOnce()>>>>;
+
+/// Tracks the `webglcontextlost` and `webglcontextrestored` events of a canvas
+pub(super) struct ContextListener {
+    target: EventTarget,
+    status: Rc<Cell<ContextStatus>>,
+    /// Called once on the next `webglcontextrestored`
+    restored_callbacks: Callbacks,
+    on_lost: Closure<dyn FnMut(Event)>,
+    on_restored: Closure<dyn FnMut(Event)>,
+}
+
+impl ContextListener {
+    pub(super) fn new(target: &EventTarget) -> Result<Self, Error> {
+        let status = Rc::new(Cell::new(ContextStatus::Ready));
+
+        let lost_status = status.clone();
+        let on_lost = Closure::wrap(Box::new(move |event: Event| {
+            // The browser only restores the context if the default is prevented
+            event.prevent_default();
+            lost_status.set(ContextStatus::Lost);
+        }) as Box<dyn FnMut(Event)>);
+
+        let restored_status = status.clone();
+        let restored_callbacks: Callbacks = Rc::default();
+        let callbacks = restored_callbacks.clone();
+        let on_restored = Closure::wrap(Box::new(move |_: Event| {
+            restored_status.set(ContextStatus::Restored);
+            let callbacks = std::mem::take(&mut *callbacks.borrow_mut());
+            for callback in callbacks {
+                callback();
+            }
+        }) as Box<dyn FnMut(Event)>);
+
+        target
+            .add_event_listener_with_callback("webglcontextlost", on_lost.as_ref().unchecked_ref())
+            .map_err(Error::js("Couldn't listen to webglcontextlost"))?;
+        target
+            .add_event_listener_with_callback(
+                "webglcontextrestored",
+                on_restored.as_ref().unchecked_ref(),
+            )
+            .map_err(Error::js("Couldn't listen to webglcontextrestored"))?;
+
+        Ok(Self {
+            target: target.clone(),
+            status,
+            restored_callbacks,
+            on_lost,
+            on_restored,
+        })
+    }
+
+    pub(super) fn status(&self) -> ContextStatus {
+        self.status.get()
+    }
+
+    pub(super) fn set_ready(&self) {
+        self.status.set(ContextStatus::Ready);
+    }
+
+    pub(super) fn on_restored(&self, callback: Box<dyn FnOnce()>) {
+        self.restored_callbacks.borrow_mut().push(callback);
+    }
+}
+
+impl Drop for ContextListener {
+    fn drop(&mut self) {
+        let _ = self.target.remove_event_listener_with_callback(
+            "webglcontextlost",
+            self.on_lost.as_ref().unchecked_ref(),
+        );
+        let _ = self.target.remove_event_listener_with_callback(
+            "webglcontextrestored",
+            self.on_restored.as_ref().unchecked_ref(),
+        );
+    }
+}
@@ -0,0 +1,539 @@
+use std::{cell::RefCell, rc::Rc};
+
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::*;
+
+use super::{
+    context::GlContext,
+    texture::{GlTexture, TextureFormat},
+};
+use crate::error::Error;
+
+const FRAMEBUFFER: u32 = WebGl2RenderingContext::FRAMEBUFFER;
+const RENDERBUFFER: u32 = WebGl2RenderingContext::RENDERBUFFER;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attachment {
+    None,
+    /// Can be sampled after rendering
+    Texture(TextureFormat),
+    /// Cheaper, but can only be read back or blitted
+    Renderbuffer(TextureFormat),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FramebufferOptions {
+    pub color: Attachment,
+    pub depth: Attachment,
+    /// MSAA samples, 0 disables multisampling. Multisampled framebuffers
+    /// only support renderbuffer attachments and must be resolved with `blit`.
+    pub samples: i32,
+}
+
+impl Default for FramebufferOptions {
+    fn default() -> Self {
+        Self {
+            color: Attachment::Texture(TextureFormat::Rgba8),
+            depth: Attachment::Renderbuffer(TextureFormat::Depth24),
+            samples: 0,
+        }
+    }
+}
+
+pub struct GlRenderbuffer {
+    shared: Rc<SharedRenderbuffer>,
+}
+
+struct SharedRenderbuffer {
+    context: GlContext,
+    renderbuffer: RefCell<WebGlRenderbuffer>,
+    format: TextureFormat,
+    samples: i32,
+    width: i32,
+    height: i32,
+}
+
+impl GlRenderbuffer {
+    pub(super) fn new(
+        context: &GlContext,
+        format: TextureFormat,
+        samples: i32,
+        width: i32,
+        height: i32,
+    ) -> Result<GlRenderbuffer, Error> {
+        let renderbuffer = GlRenderbuffer {
+            shared: Rc::new(SharedRenderbuffer {
+                renderbuffer: RefCell::new(create_renderbuffer(context)?),
+                context: context.clone(),
+                format,
+                samples,
+                width,
+                height,
+            }),
+        };
+        renderbuffer.allocate();
+        context.register(&renderbuffer.shared, |shared| {
+            *shared.renderbuffer.borrow_mut() = create_renderbuffer(&shared.context)?;
+            GlRenderbuffer { shared }.allocate();
+            Ok(())
+        });
+        Ok(renderbuffer)
+    }
+
+    pub fn bind(&self) {
+        let shared = &self.shared;
+        shared
+            .context
+            .bind_renderbuffer(RENDERBUFFER, Some(&shared.renderbuffer.borrow()));
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.shared.format
+    }
+
+    pub fn samples(&self) -> i32 {
+        self.shared.samples
+    }
+
+    fn allocate(&self) {
+        let shared = &self.shared;
+        self.bind();
+        if shared.samples > 0 {
+            shared.context.renderbuffer_storage_multisample(
+                RENDERBUFFER,
+                shared.samples,
+                shared.format.internal_format(),
+                shared.width,
+                shared.height,
+            );
+        } else {
+            shared.context.renderbuffer_storage(
+                RENDERBUFFER,
+                shared.format.internal_format(),
+                shared.width,
+                shared.height,
+            );
+        }
+    }
+}
+
+impl Drop for SharedRenderbuffer {
+    fn drop(&mut self) {
+        self.context
+            .delete_renderbuffer(Some(&self.renderbuffer.borrow()));
+    }
+}
+
+enum Target {
+    None,
+    Texture(GlTexture),
+    Renderbuffer(GlRenderbuffer),
+}
+
+pub struct GlFramebuffer {
+    shared: Rc<SharedFramebuffer>,
+    options: FramebufferOptions,
+    width: i32,
+    height: i32,
+}
+
+struct SharedFramebuffer {
+    context: GlContext,
+    framebuffer: RefCell<WebGlFramebuffer>,
+    color: RefCell<Target>,
+    depth: RefCell<Target>,
+}
+
+impl GlFramebuffer {
+    pub(super) fn new(
+        context: &GlContext,
+        options: FramebufferOptions,
+        width: i32,
+        height: i32,
+    ) -> Result<GlFramebuffer, Error> {
+        if let Attachment::Texture(format) | Attachment::Renderbuffer(format) = options.color {
+            if format.is_depth() {
+                return Err(Error::Invalid(format!(
+                    "{:?} can't be used as a color attachment",
+                    format
+                )));
+            }
+        }
+        if let Attachment::Texture(format) | Attachment::Renderbuffer(format) = options.depth {
+            if !format.is_depth() {
+                return Err(Error::Invalid(format!(
+                    "{:?} can't be used as a depth attachment",
+                    format
+                )));
+            }
+        }
+        if options.samples > 0 {
+            if let Attachment::Texture(_) = options.color {
+                return Err(Error::Invalid(String::from(
+                    "Multisampled framebuffers can't have texture attachments",
+                )));
+            }
+            if let Attachment::Texture(_) = options.depth {
+                return Err(Error::Invalid(String::from(
+                    "Multisampled framebuffers can't have texture attachments",
+                )));
+            }
+        }
+
+        enable_format(context, options.color)?;
+
+        let mut framebuffer = GlFramebuffer {
+            shared: Rc::new(SharedFramebuffer {
+                framebuffer: RefCell::new(create_framebuffer(context)?),
+                context: context.clone(),
+                color: RefCell::new(Target::None),
+                depth: RefCell::new(Target::None),
+            }),
+            options,
+            width: 0,
+            height: 0,
+        };
+        framebuffer.resize(width, height)?;
+
+        // The attachments restore themselves, they are attached again once restored
+        context.register(&framebuffer.shared, |shared| {
+            *shared.framebuffer.borrow_mut() = create_framebuffer(&shared.context)?;
+            Ok(())
+        });
+        let color = options.color;
+        context.register_link(&framebuffer.shared, move |shared| {
+            enable_format(&shared.context, color)?;
+            shared.attach();
+            Ok(())
+        });
+        Ok(framebuffer)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn options(&self) -> FramebufferOptions {
+        self.options
+    }
+
+    pub fn color_texture(&self) -> Option<GlTexture> {
+        self.shared.color.borrow().texture()
+    }
+
+    pub fn depth_texture(&self) -> Option<GlTexture> {
+        self.shared.depth.borrow().texture()
+    }
+
+    /// Reallocates the attachments, their previous content is lost
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), Error> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+
+        *self.shared.color.borrow_mut() = self.create_target(self.options.color, width, height)?;
+        *self.shared.depth.borrow_mut() = self.create_target(self.options.depth, width, height)?;
+        self.width = width;
+        self.height = height;
+
+        let status = self.shared.attach();
+
+        if status == WebGl2RenderingContext::FRAMEBUFFER_COMPLETE {
+            Ok(())
+        } else {
+            Err(Error::Allocation(format!(
+                "Framebuffer {}x{} with {:?} is incomplete (status {:#x})",
+                width, height, self.options, status
+            )))
+        }
+    }
+
+    /// Following draws will render into this framebuffer.
+    /// Use `Gl::bind_canvas` to go back to the canvas.
+    pub fn bind(&self) {
+        self.shared
+            .context
+            .bind_framebuffer(FRAMEBUFFER, Some(&self.shared.framebuffer.borrow()));
+        self.shared.context.viewport(0, 0, self.width, self.height);
+    }
+
+    /// Integer color attachments are cleared to `color` truncated to integers
+    pub fn clear(&self, color: &[f32; 4]) {
+        self.bind();
+        if self.color_format().is_some_and(|format| format.is_integer()) {
+            self.shared.context.clear_bufferuiv_with_u32_array(
+                WebGl2RenderingContext::COLOR,
+                0,
+                &color.map(|c| c as u32),
+            );
+            self.shared.context.clear(WebGl2RenderingContext::DEPTH_BUFFER_BIT);
+        } else {
+            self.shared.context
+                .clear_color(color[0], color[1], color[2], color[3]);
+            self.shared.context.clear(
+                WebGl2RenderingContext::COLOR_BUFFER_BIT
+                    | WebGl2RenderingContext::DEPTH_BUFFER_BIT,
+            );
+        }
+    }
+
+    /// Reads RGBA8 pixels of the color attachment, rows go from bottom to top
+    pub fn read_pixels(&self, x: i32, y: i32, width: i32, height: i32) -> Result<Vec<u8>, Error> {
+        match self.options.color {
+            Attachment::Texture(TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8)
+            | Attachment::Renderbuffer(TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8) => {}
+            color => {
+                return Err(Error::Invalid(format!(
+                    "Can't read RGBA8 pixels from {:?}",
+                    color
+                )))
+            }
+        }
+        self.check_region(x, y, width, height)?;
+
+        let mut pixels = vec![0; (width * height * 4) as usize];
+        self.read(|context| {
+            context.pixel_storei(WebGl2RenderingContext::PACK_ALIGNMENT, 1);
+            context.read_pixels_with_opt_u8_array(
+                x,
+                y,
+                width,
+                height,
+                WebGl2RenderingContext::RGBA,
+                WebGl2RenderingContext::UNSIGNED_BYTE,
+                Some(&mut pixels),
+            )
+        })?;
+        Ok(pixels)
+    }
+
+    /// Reads the RGBA unsigned integers of an integer color attachment,
+    /// rows go from bottom to top
+    pub fn read_pixels_u32(
+        &self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> Result<Vec<u32>, Error> {
+        if !self
+            .color_format()
+            .is_some_and(|format| format.is_integer())
+        {
+            return Err(Error::Invalid(format!(
+                "Can't read integer pixels from {:?}",
+                self.options.color
+            )));
+        }
+        self.check_region(x, y, width, height)?;
+
+        let pixels = js_sys::Uint32Array::new_with_length((width * height * 4) as u32);
+        self.read(|context| {
+            context.read_pixels_with_opt_array_buffer_view(
+                x,
+                y,
+                width,
+                height,
+                WebGl2RenderingContext::RGBA_INTEGER,
+                WebGl2RenderingContext::UNSIGNED_INT,
+                Some(&pixels),
+            )
+        })?;
+        Ok(pixels.to_vec())
+    }
+
+    /// Runs `read` with this framebuffer bound for reading, then binds back the
+    /// previous one. The draw framebuffer and the viewport are left as they are.
+    fn read(
+        &self,
+        read: impl FnOnce(&WebGl2RenderingContext) -> Result<(), JsValue>,
+    ) -> Result<(), Error> {
+        let context = &self.shared.context;
+        let previous = context
+            .get_parameter(WebGl2RenderingContext::READ_FRAMEBUFFER_BINDING)
+            .ok()
+            .and_then(|binding| binding.dyn_into::<WebGlFramebuffer>().ok());
+        context.bind_framebuffer(
+            WebGl2RenderingContext::READ_FRAMEBUFFER,
+            Some(&self.shared.framebuffer.borrow()),
+        );
+        let result = read(context);
+        context.bind_framebuffer(WebGl2RenderingContext::READ_FRAMEBUFFER, previous.as_ref());
+        result.map_err(Error::js("Couldn't read framebuffer pixels"))
+    }
+
+    /// Copies the color attachment into `target` (or the canvas when `None`), stretching it to
+    /// `target_size`. Resolves multisampled framebuffers, which can't be stretched.
+    pub fn blit(
+        &self,
+        target: Option<&GlFramebuffer>,
+        target_size: (i32, i32),
+    ) -> Result<(), Error> {
+        let same_size = target_size == (self.width, self.height);
+        if self.options.samples > 0 && !same_size {
+            return Err(Error::Invalid(format!(
+                "Multisampled {}x{} framebuffer can't be resolved into {}x{}",
+                self.width, self.height, target_size.0, target_size.1
+            )));
+        }
+
+        self.shared.context.bind_framebuffer(
+            WebGl2RenderingContext::READ_FRAMEBUFFER,
+            Some(&self.shared.framebuffer.borrow()),
+        );
+        self.shared.context.bind_framebuffer(
+            WebGl2RenderingContext::DRAW_FRAMEBUFFER,
+            target
+                .map(|target| target.shared.framebuffer.borrow().clone())
+                .as_ref(),
+        );
+        self.shared.context.blit_framebuffer(
+            0,
+            0,
+            self.width,
+            self.height,
+            0,
+            0,
+            target_size.0,
+            target_size.1,
+            WebGl2RenderingContext::COLOR_BUFFER_BIT,
+            if same_size {
+                WebGl2RenderingContext::NEAREST
+            } else {
+                WebGl2RenderingContext::LINEAR
+            },
+        );
+        self.shared.context.bind_framebuffer(FRAMEBUFFER, None);
+        Ok(())
+    }
+
+    fn color_format(&self) -> Option<TextureFormat> {
+        match self.options.color {
+            Attachment::Texture(format) | Attachment::Renderbuffer(format) => Some(format),
+            Attachment::None => None,
+        }
+    }
+
+    fn check_region(&self, x: i32, y: i32, width: i32, height: i32) -> Result<(), Error> {
+        if x < 0
+            || y < 0
+            || width < 0
+            || height < 0
+            || x + width > self.width
+            || y + height > self.height
+        {
+            Err(Error::Invalid(format!(
+                "Region {}x{} at ({}, {}) is out of a {}x{} framebuffer",
+                width, height, x, y, self.width, self.height
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn create_target(
+        &self,
+        attachment: Attachment,
+        width: i32,
+        height: i32,
+    ) -> Result<Target, Error> {
+        let context = &self.shared.context;
+        Ok(match attachment {
+            Attachment::None => Target::None,
+            Attachment::Texture(format) => {
+                Target::Texture(GlTexture::new_2d(context, format, width, height)?)
+            }
+            Attachment::Renderbuffer(format) => Target::Renderbuffer(GlRenderbuffer::new(
+                context,
+                format,
+                self.options.samples,
+                width,
+                height,
+            )?),
+        })
+    }
+}
+
+impl SharedFramebuffer {
+    /// Attaches the targets and returns the status of the framebuffer
+    fn attach(&self) -> u32 {
+        self.context
+            .bind_framebuffer(FRAMEBUFFER, Some(&self.framebuffer.borrow()));
+        self.color
+            .borrow()
+            .attach(&self.context, WebGl2RenderingContext::COLOR_ATTACHMENT0);
+        self.depth
+            .borrow()
+            .attach(&self.context, WebGl2RenderingContext::DEPTH_ATTACHMENT);
+
+        let status = self.context.check_framebuffer_status(FRAMEBUFFER);
+        self.context.bind_framebuffer(FRAMEBUFFER, None);
+        status
+    }
+}
+
+impl Drop for SharedFramebuffer {
+    fn drop(&mut self) {
+        self.context
+            .delete_framebuffer(Some(&self.framebuffer.borrow()));
+    }
+}
+
+impl Target {
+    fn texture(&self) -> Option<GlTexture> {
+        match self {
+            Target::Texture(texture) => Some(texture.share()),
+            _ => None,
+        }
+    }
+
+    fn attach(&self, context: &WebGl2RenderingContext, attachment: u32) {
+        match self {
+            Target::None => {}
+            Target::Texture(texture) => context.framebuffer_texture_2d(
+                FRAMEBUFFER,
+                attachment,
+                WebGl2RenderingContext::TEXTURE_2D,
+                Some(&texture.gl_texture()),
+                0,
+            ),
+            Target::Renderbuffer(renderbuffer) => context.framebuffer_renderbuffer(
+                FRAMEBUFFER,
+                attachment,
+                RENDERBUFFER,
+                Some(&renderbuffer.shared.renderbuffer.borrow()),
+            ),
+        }
+    }
+}
+
+fn create_renderbuffer(context: &WebGl2RenderingContext) -> Result<WebGlRenderbuffer, Error> {
+    context
+        .create_renderbuffer()
+        .ok_or_else(|| Error::Allocation(String::from("Unable to create gl renderbuffer")))
+}
+
+fn create_framebuffer(context: &WebGl2RenderingContext) -> Result<WebGlFramebuffer, Error> {
+    context
+        .create_framebuffer()
+        .ok_or_else(|| Error::Allocation(String::from("Unable to create gl framebuffer")))
+}
+
+/// Float formats can only be rendered to with `EXT_color_buffer_float`
+fn enable_format(context: &WebGl2RenderingContext, color: Attachment) -> Result<(), Error> {
+    if let Attachment::Texture(format) | Attachment::Renderbuffer(format) = color {
+        if format.is_float() {
+            context
+                .get_extension("EXT_color_buffer_float")
+                .map_err(Error::js("Couldn't get EXT_color_buffer_float"))?
+                .ok_or_else(|| {
+                    Error::Context(String::from("Float render targets are not supported"))
+                })?;
+        }
+    }
+    Ok(())
+}
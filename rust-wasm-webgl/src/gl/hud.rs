@@ -0,0 +1,291 @@
+use super::*;
+use crate::stats::FrameStats;
+
+/// Quad from 0 to 1, placed by its top left corner
+const QUAD_VERTICES: [f32; 12] = [0., 0., 0., 1., 1., 0., 1., 1., 1., 0., 0., 1.];
+
+const MARGIN: f32 = 8.;
+const PADDING: f32 = 6.;
+const GRAPH_HEIGHT: f32 = 40.;
+/// Frame time at the top of the graph, in budgets
+const GRAPH_RANGE: f32 = 2.;
+/// Font dots from a character to the next
+const ADVANCE: f32 = 4.;
+/// Font dots from a line to the next
+const LINE_HEIGHT: f32 = 7.;
+
+const BACKGROUND: [f32; 4] = [0.05, 0.05, 0.08, 1.];
+const TEXT: [f32; 4] = [0.9, 0.9, 0.9, 1.];
+const GOOD: [f32; 4] = [0.3, 0.8, 0.3, 1.];
+const SLOW: [f32; 4] = [0.9, 0.7, 0.2, 1.];
+const JANK: [f32; 4] = [0.9, 0.25, 0.2, 1.];
+const BUDGET_LINE: [f32; 4] = [0.5, 0.5, 0.5, 1.];
+
+/// Performance overlay in the top left corner of the canvas: fps, a graph of
+/// the frame times, the phases of `FrameStats` and the `RenderCounters` of the
+/// last frame. Drawn with its own mesh on top of the frame, before `Gl::present`.
+pub struct Hud {
+    mesh: GlMesh,
+    visible: bool,
+    /// CSS pixels per dot of the font
+    scale: f32,
+}
+
+impl Hud {
+    pub(super) fn new(gl: &Gl) -> Result<Self, Error> {
+        let mut mesh = gl.create_mesh(&QUAD_VERTICES)?;
+        mesh.set_culling(false);
+        Ok(Self {
+            mesh,
+            visible: true,
+            scale: 2.,
+        })
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(1.);
+    }
+
+    /// The camera of `gl` is kept, and the overlay isn't counted in its counters
+    pub fn draw(&mut self, gl: &mut Gl, stats: &FrameStats) -> Result<(), Error> {
+        if !self.visible {
+            return Ok(());
+        }
+
+        let summary = stats.summary();
+        let counters = gl.counters();
+        let mut lines = vec![
+            format!(
+                "FPS {:.0}  {:.1} MS",
+                1. / summary.delta.mean.max(f32::EPSILON),
+                summary.delta.mean * 1000.
+            ),
+            format!(
+                "WORK {:.2} MS  P99 {:.2}",
+                summary.work.mean * 1000.,
+                summary.work.p99 * 1000.
+            ),
+        ];
+        for (name, phase) in &summary.phases {
+            lines.push(format!("{} {:.2} MS", name, phase.mean * 1000.));
+        }
+        lines.push(format!(
+            "DRAWS {}  INSTANCES {}",
+            counters.draw_calls, counters.instances
+        ));
+        lines.push(format!(
+            "UPLOAD {:.1} KB  JANKS {}",
+            counters.uploaded_bytes as f32 / 1024.,
+            summary.janks
+        ));
+
+        let dot = self.scale;
+        let line_height = LINE_HEIGHT * dot;
+        let bar_width = dot.max(1.);
+        let text_width = lines
+            .iter()
+            .map(|line| line.chars().count() as f32 * ADVANCE * dot)
+            .fold(0., f32::max);
+        let graph_width = summary.frames as f32 * bar_width;
+        let width = text_width.max(graph_width) + 2. * PADDING;
+        let height = GRAPH_HEIGHT + lines.len() as f32 * line_height + 3. * PADDING;
+
+        self.rect([MARGIN, MARGIN], [width, height], &BACKGROUND);
+
+        let (x, mut y) = (MARGIN + PADDING, MARGIN + PADDING);
+        let budget = stats.budget();
+        let graph_bottom = y + GRAPH_HEIGHT;
+        let budget_height = GRAPH_HEIGHT / GRAPH_RANGE;
+        for (i, sample) in stats.samples().enumerate() {
+            let bar_height = (sample.delta / budget * budget_height).clamp(1., GRAPH_HEIGHT);
+            let color = if stats.is_jank(sample) {
+                &JANK
+            } else if sample.delta > budget {
+                &SLOW
+            } else {
+                &GOOD
+            };
+            self.rect(
+                [x + i as f32 * bar_width, graph_bottom - bar_height],
+                [bar_width, bar_height],
+                color,
+            );
+        }
+        self.rect(
+            [x, graph_bottom - budget_height],
+            [graph_width.max(text_width), 1.],
+            &BUDGET_LINE,
+        );
+        y = graph_bottom + PADDING;
+
+        self.text(&lines.join("\n"), [x, y], &TEXT);
+
+        // Drawn in CSS pixels on top of everything, then the frame's camera is set back
+        let camera_data = gl.camera_data;
+        let viewport = gl.viewport;
+        let frame_counters = gl.counters.get();
+
+        gl.set_pixel_camera(&PixelCamera::new(PixelResolution::Css));
+        gl.context.disable(WebGl2RenderingContext::DEPTH_TEST);
+        self.mesh.draw();
+        gl.context.enable(WebGl2RenderingContext::DEPTH_TEST);
+
+        let mut matrix = IDENTITY_MAT4;
+        matrix.copy_from_slice(&camera_data[..16]);
+        gl.set_camera_data(&matrix, camera_data[16] > 0., viewport);
+        gl.counters.set(frame_counters);
+        Ok(())
+    }
+
+    fn rect(&mut self, position: [f32; 2], size: [f32; 2], color: &[f32; 4]) {
+        self.mesh.create_instance(
+            InstanceProperties::new()
+                .position(&[position[0], position[1], 0.])
+                .color(color)
+                .matrix(&[size[0], 0., 0., size[1]]),
+        );
+    }
+
+    fn text(&mut self, text: &str, position: [f32; 2], color: &[f32; 4]) {
+        let dot = self.scale;
+        for position in text_dots(text, position, dot) {
+            self.rect(position, [dot, dot], color);
+        }
+    }
+}
+
+/// Top left corners of the `dot` sized squares of `text`, uppercase, characters
+/// missing from the font are left blank
+fn text_dots(text: &str, position: [f32; 2], dot: f32) -> Vec<[f32; 2]> {
+    let mut dots = vec![];
+    for (line_index, line) in text.split('\n').enumerate() {
+        let top = position[1] + line_index as f32 * LINE_HEIGHT * dot;
+        for (i, c) in line.chars().enumerate() {
+            let rows = match glyph(c.to_ascii_uppercase()) {
+                Some(rows) => rows,
+                None => continue,
+            };
+            let left = position[0] + i as f32 * ADVANCE * dot;
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) != 0 {
+                        dots.push([left + column as f32 * dot, top + row as f32 * dot]);
+                    }
+                }
+            }
+        }
+    }
+    dots
+}
+
+/// 3x5 dots, a row per byte with the leftmost dot in the highest of 3 bits
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b010, 0b100, 0b100, 0b100, 0b010],
+        ')' => [0b010, 0b001, 0b001, 0b001, 0b010],
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_characters_are_blank_but_advance() {
+        assert_eq!(glyph('#'), None);
+        assert_eq!(glyph('a'), None);
+        assert!(text_dots("#~", [0., 0.], 1.).is_empty());
+        assert_eq!(text_dots("#.", [0., 0.], 1.), [[5., 4.]]);
+    }
+
+    #[test]
+    fn lowercase_is_drawn_uppercase() {
+        assert_eq!(
+            text_dots("fps", [3., 4.], 2.),
+            text_dots("FPS", [3., 4.], 2.)
+        );
+    }
+
+    #[test]
+    fn draws_a_quad_per_dot() {
+        assert_eq!(text_dots("8", [0., 0.], 1.).len(), 13);
+        assert_eq!(text_dots("10", [0., 0.], 1.).len(), 8 + 12);
+        assert_eq!(text_dots("1 1", [0., 0.], 1.).len(), 16);
+    }
+
+    #[test]
+    fn lines_start_below_each_other() {
+        let dot = 2.;
+        let line = text_dots("1:", [10., 20.], dot);
+        let lines = text_dots("1:\n-\n1:", [10., 20.], dot);
+        let below = |dots: &[[f32; 2]], lines: f32| -> Vec<[f32; 2]> {
+            dots.iter()
+                .map(|&[x, y]| [x, y + lines * LINE_HEIGHT * dot])
+                .collect()
+        };
+
+        assert_eq!(lines.len(), 2 * line.len() + 3);
+        assert_eq!(lines[..line.len()], line[..]);
+        assert_eq!(
+            lines[line.len()..line.len() + 3],
+            below(&text_dots("-", [10., 20.], dot), 1.)[..]
+        );
+        assert_eq!(lines[line.len() + 3..], below(&line, 2.)[..]);
+        assert_eq!(lines[line.len()], [10., 20. + (LINE_HEIGHT + 2.) * dot]);
+    }
+}
@@ -0,0 +1,249 @@
+/// Column major 4x4 matrix, as expected by the shaders
+pub type Mat4 = [f32; 16];
+
+pub const IDENTITY_MAT4: Mat4 = [
+    1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1.,
+];
+
+/// `a * b`, so `b` is applied first
+pub fn mat4_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut result = [0.; 16];
+    for column in 0..4 {
+        for row in 0..4 {
+            result[column * 4 + row] = (0..4).map(|i| a[i * 4 + row] * b[column * 4 + i]).sum();
+        }
+    }
+    result
+}
+
+pub fn mat4_transform_point(m: &Mat4, p: [f32; 3]) -> [f32; 3] {
+    let w = m[3] * p[0] + m[7] * p[1] + m[11] * p[2] + m[15];
+    [
+        (m[0] * p[0] + m[4] * p[1] + m[8] * p[2] + m[12]) / w,
+        (m[1] * p[0] + m[5] * p[1] + m[9] * p[2] + m[13]) / w,
+        (m[2] * p[0] + m[6] * p[1] + m[10] * p[2] + m[14]) / w,
+    ]
+}
+
+pub fn mat4_translation(t: [f32; 3]) -> Mat4 {
+    let mut m = IDENTITY_MAT4;
+    m[12..15].copy_from_slice(&t);
+    m
+}
+
+pub fn mat4_scaling(s: [f32; 3]) -> Mat4 {
+    let mut m = IDENTITY_MAT4;
+    m[0] = s[0];
+    m[5] = s[1];
+    m[10] = s[2];
+    m
+}
+
+/// Rotation of `angle` radians around `axis`
+pub fn mat4_rotation(axis: [f32; 3], angle: f32) -> Mat4 {
+    let [x, y, z] = normalize(axis);
+    let (s, c) = angle.sin_cos();
+    let t = 1. - c;
+    [
+        t * x * x + c,
+        t * x * y + s * z,
+        t * x * z - s * y,
+        0.,
+        t * x * y - s * z,
+        t * y * y + c,
+        t * y * z + s * x,
+        0.,
+        t * x * z + s * y,
+        t * y * z - s * x,
+        t * z * z + c,
+        0.,
+        0.,
+        0.,
+        0.,
+        1.,
+    ]
+}
+
+pub fn mat4_look_at(eye: [f32; 3], target: [f32; 3], up: [f32; 3]) -> Mat4 {
+    let forward = normalize(sub(target, eye));
+    let side = normalize(cross(forward, up));
+    let up = cross(side, forward);
+    [
+        side[0],
+        up[0],
+        -forward[0],
+        0.,
+        side[1],
+        up[1],
+        -forward[1],
+        0.,
+        side[2],
+        up[2],
+        -forward[2],
+        0.,
+        -dot(side, eye),
+        -dot(up, eye),
+        dot(forward, eye),
+        1.,
+    ]
+}
+
+pub fn mat4_perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Mat4 {
+    let f = 1. / (fov_y / 2.).tan();
+    let range = near - far;
+    [
+        f / aspect,
+        0.,
+        0.,
+        0.,
+        0.,
+        f,
+        0.,
+        0.,
+        0.,
+        0.,
+        (far + near) / range,
+        -1.,
+        0.,
+        0.,
+        2. * far * near / range,
+        0.,
+    ]
+}
+
+pub fn mat4_orthographic(
+    left: f32,
+    right: f32,
+    bottom: f32,
+    top: f32,
+    near: f32,
+    far: f32,
+) -> Mat4 {
+    [
+        2. / (right - left),
+        0.,
+        0.,
+        0.,
+        0.,
+        2. / (top - bottom),
+        0.,
+        0.,
+        0.,
+        0.,
+        -2. / (far - near),
+        0.,
+        -(right + left) / (right - left),
+        -(top + bottom) / (top - bottom),
+        -(far + near) / (far - near),
+        1.,
+    ]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = dot(v, v).sqrt();
+    if len > 0. {
+        [v[0] / len, v[1] / len, v[2] / len]
+    } else {
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: [f32; 3], expected: [f32; 3]) {
+        assert!(
+            value
+                .iter()
+                .zip(expected)
+                .all(|(v, e)| (v - e).abs() < 1e-5),
+            "expected {:?} but found {:?}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn multiplies_with_the_right_matrix_applied_first() {
+        let m = mat4_rotation([1., 2., 3.], 0.8);
+        assert_eq!(mat4_mul(&IDENTITY_MAT4, &m), m);
+        assert_eq!(mat4_mul(&m, &IDENTITY_MAT4), m);
+
+        let scale_then_move = mat4_mul(&mat4_translation([1., 2., 3.]), &mat4_scaling([2.; 3]));
+        assert_close(
+            mat4_transform_point(&scale_then_move, [1., 1., 1.]),
+            [3., 4., 5.],
+        );
+    }
+
+    #[test]
+    fn rotates_counterclockwise_around_the_axis() {
+        let quarter = std::f32::consts::FRAC_PI_2;
+        let rotation = mat4_rotation([0., 0., 2.], quarter);
+        assert_close(mat4_transform_point(&rotation, [1., 0., 0.]), [0., 1., 0.]);
+        let rotation = mat4_rotation([1., 0., 0.], quarter);
+        assert_close(mat4_transform_point(&rotation, [0., 1., 0.]), [0., 0., 1.]);
+    }
+
+    #[test]
+    fn look_at_puts_the_eye_at_the_origin_looking_down_z() {
+        let view = mat4_look_at([1., 2., 3.], [1., 2., -7.], [0., 1., 0.]);
+        assert_close(mat4_transform_point(&view, [1., 2., 3.]), [0., 0., 0.]);
+        assert_close(mat4_transform_point(&view, [1., 2., -7.]), [0., 0., -10.]);
+        assert_close(mat4_transform_point(&view, [2., 3., 3.]), [1., 1., 0.]);
+
+        let view = mat4_look_at([5., 0., 0.], [0., 0., 0.], [0., 1., 0.]);
+        assert_close(mat4_transform_point(&view, [0., 0., 0.]), [0., 0., -5.]);
+        assert_close(mat4_transform_point(&view, [5., 0., -1.]), [1., 0., 0.]);
+    }
+
+    #[test]
+    fn perspective_maps_near_and_far_to_the_clip_range() {
+        let projection = mat4_perspective(std::f32::consts::FRAC_PI_2, 2., 0.5, 10.);
+        assert_close(
+            mat4_transform_point(&projection, [0., 0., -0.5]),
+            [0., 0., -1.],
+        );
+        assert_close(
+            mat4_transform_point(&projection, [0., 0., -10.]),
+            [0., 0., 1.],
+        );
+        // 90 degrees, the top of the view is as far up as it is deep. The depth
+        // is (far + near) / (far - near) - 2 * far * near / ((far - near) * distance)
+        let depth = 10.5 / 9.5 - 10. / 19.;
+        assert_close(
+            mat4_transform_point(&projection, [2., 2., -2.]),
+            [0.5, 1., depth],
+        );
+    }
+
+    #[test]
+    fn orthographic_maps_the_box_to_the_clip_cube() {
+        let projection = mat4_orthographic(-4., 2., -1., 3., 1., 5.);
+        assert_close(
+            mat4_transform_point(&projection, [-4., -1., -1.]),
+            [-1., -1., -1.],
+        );
+        assert_close(
+            mat4_transform_point(&projection, [2., 3., -5.]),
+            [1., 1., 1.],
+        );
+    }
+}
@@ -1,18 +1,59 @@
-use crate::gl::vao::Attrib;
+use std::{cell::Cell, marker::PhantomData};
+
+use crate::{gl::vao::Attrib, spatial::Aabb};
 
 use super::{buffer::GlBuffer, vao::GlVAO, *};
 
-pub struct GlMesh {
-    context: WebGl2RenderingContext,
+/// Per instance data of a mesh, laid out as the attributes of its shader
+pub trait Instance {
+    /// Coordinates of each mesh vertex
+    const VERTEX_LEN: i32;
+    const ATTRIBUTES: &'static [Attrib];
+    /// Vertex shader used by `Picker`, with the same transform as the mesh shader
+    const PICK_VERTEX_SOURCE: &'static str;
+    /// Length of `data`
+    const DATA_LEN: usize;
+    fn data(&self) -> &[f32];
+    /// Transform from the mesh vertices to the world of the instance stored as `data`,
+    /// used for culling
+    fn model_matrix(data: &[f32]) -> Mat4;
+}
+
+/// Instances of the last `GlMesh::draw`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CullStats {
+    pub drawn: u32,
+    /// Outside of the view, not uploaded
+    pub culled: u32,
+}
+
+impl std::ops::AddAssign for CullStats {
+    fn add_assign(&mut self, other: Self) {
+        self.drawn += other.drawn;
+        self.culled += other.culled;
+    }
+}
+
+pub struct GlMesh<I: Instance = InstanceProperties> {
+    context: GlContext,
+    program: Rc<GlProgram>,
     instances_data: Vec<f32>,
     instances_buffer: GlBuffer,
     vertices_buffer: GlBuffer,
     vao: GlVAO,
     vertices_count: i32,
     instances_count: i32,
+    /// Shared with `Gl`, updated when the camera changes
+    view_projection: Rc<Cell<Mat4>>,
+    counters: Rc<Cell<RenderCounters>>,
+    /// Corners of the box around the vertices
+    bounds: [[f32; 3]; 2],
+    culling: bool,
+    stats: CullStats,
+    instance: PhantomData<I>,
 }
 
-pub struct InstanceProperties([f32; 11]);
+pub struct InstanceProperties(pub(super) [f32; 11]);
 impl InstanceProperties {
     pub fn new() -> Self {
         // let n = 2f32.sqrt() / 2.;
@@ -51,64 +92,347 @@ impl InstanceProperties {
     }
 }
 
-impl GlMesh {
-    pub fn create_instance(&mut self, instance: &InstanceProperties) {
-        self.instances_data.extend_from_slice(&instance.0);
+impl InstanceProperties {
+    /// World bounds of the instance of a mesh whose vertices fit in `local`,
+    /// see `Aabb::of_points`
+    pub fn bounds(&self, local: &Aabb) -> Aabb {
+        let [a, b, c, d] = [self.0[7], self.0[8], self.0[9], self.0[10]];
+        // The shaders compute `vertex_coord * matrix`
+        let transform = |[x, y]: [f32; 2]| [x * a + y * b, x * c + y * d];
+
+        let [cx, cy] = transform(local.center());
+        let [hx, hy] = local.half_size();
+        Aabb::from_center(
+            [self.0[0] + cx, self.0[1] + cy],
+            [hx * a.abs() + hy * b.abs(), hx * c.abs() + hy * d.abs()],
+        )
+    }
+}
+
+impl Instance for InstanceProperties {
+    const VERTEX_LEN: i32 = 2;
+    const ATTRIBUTES: &'static [Attrib] = &[
+        Attrib::VecF32(1, 3),
+        Attrib::VecF32(2, 4),
+        Attrib::MatF32(3, 2, 2),
+    ];
+    const PICK_VERTEX_SOURCE: &'static str = shaders::PICK_VERTEX_SOURCE;
+    const DATA_LEN: usize = 11;
+    fn data(&self) -> &[f32] {
+        &self.0
+    }
+    fn model_matrix(data: &[f32]) -> Mat4 {
+        let [a, b, c, d] = [data[7], data[8], data[9], data[10]];
+        [
+            a, c, 0., 0., b, d, 0., 0., 0., 0., 1., 0., data[0], data[1], data[2], 1.,
+        ]
+    }
+}
+
+/// Color and model matrix of an instance of a 3D mesh
+pub struct InstanceProperties3D(pub(super) [f32; 20]);
+impl InstanceProperties3D {
+    pub fn new() -> Self {
+        let mut data = [1.; 20];
+        data[4..20].copy_from_slice(&IDENTITY_MAT4);
+        Self(data)
+    }
+    pub fn color(&mut self, color: &[f32; 4]) -> &mut Self {
+        self.0[0..4].copy_from_slice(color);
+        self
+    }
+    pub fn transform(&mut self, model: &Mat4) -> &mut Self {
+        self.0[4..20].copy_from_slice(model);
+        self
+    }
+    pub fn model(&self) -> Mat4 {
+        let mut model = [0.; 16];
+        model.copy_from_slice(&self.0[4..20]);
+        model
+    }
+    /// The following builders apply on top of the current transform
+    pub fn translate(&mut self, translation: &[f32; 3]) -> &mut Self {
+        self.transform(&mat4_mul(&mat4_translation(*translation), &self.model()))
+    }
+    pub fn scale(&mut self, scale: &[f32; 3]) -> &mut Self {
+        self.transform(&mat4_mul(&mat4_scaling(*scale), &self.model()))
+    }
+    pub fn rotate(&mut self, axis: &[f32; 3], angle: f32) -> &mut Self {
+        self.transform(&mat4_mul(&mat4_rotation(*axis, angle), &self.model()))
+    }
+}
+
+impl Instance for InstanceProperties3D {
+    const VERTEX_LEN: i32 = 3;
+    const ATTRIBUTES: &'static [Attrib] = &[Attrib::VecF32(1, 4), Attrib::MatF32(2, 4, 4)];
+    const PICK_VERTEX_SOURCE: &'static str = shaders::PICK_VERTEX_3D_SOURCE;
+    const DATA_LEN: usize = 20;
+    fn data(&self) -> &[f32] {
+        &self.0
+    }
+    fn model_matrix(data: &[f32]) -> Mat4 {
+        let mut model = [0.; 16];
+        model.copy_from_slice(&data[4..20]);
+        model
+    }
+}
+
+impl<I: Instance> GlMesh<I> {
+    pub fn create_instance(&mut self, instance: &I) {
+        self.instances_data.extend_from_slice(instance.data());
         self.instances_count += 1;
     }
 
+    /// Draws the instances in view of the current camera
     pub fn draw(&mut self) {
+        if self.culling {
+            self.cull();
+        } else {
+            self.stats = CullStats {
+                drawn: self.instances_count as u32,
+                culled: 0,
+            };
+        }
+        if self.instances_count > 0 {
+            let program = self.program.clone();
+            self.draw_with(&program);
+        }
+
+        self.instances_count = 0;
+        self.instances_data.truncate(0);
+    }
+
+    pub fn instances_count(&self) -> i32 {
+        self.instances_count
+    }
+
+    /// Enabled by default, disable it for shaders moving vertices outside of the mesh bounds
+    pub fn set_culling(&mut self, culling: bool) {
+        self.culling = culling;
+    }
+
+    pub fn cull_stats(&self) -> CullStats {
+        self.stats
+    }
+
+    /// Removes the instances whose bounds are outside of the clip space
+    fn cull(&mut self) {
+        self.stats = cull_instances::<I>(
+            &mut self.instances_data,
+            &self.view_projection.get(),
+            &self.bounds,
+        );
+        self.instances_count = self.stats.drawn as i32;
+    }
+
+    /// Draws the instances created since the last `draw` with another program,
+    /// keeping them for the next draw
+    pub(super) fn draw_with(&mut self, program: &GlProgram) {
         self.instances_buffer.update(&self.instances_data);
+        RenderCounters::add(&self.counters, 1, self.instances_count, &self.instances_data);
 
+        program.bind();
         self.vao.bind();
 
         const PRIMITIVE: u32 = WebGl2RenderingContext::TRIANGLES;
         self.context
             .draw_arrays_instanced(PRIMITIVE, 0, self.vertices_count, self.instances_count);
-
-        self.instances_count = 0;
-        self.instances_data.truncate(0);
     }
 
     pub(super) fn from_vertices(
-        context: &WebGl2RenderingContext,
+        context: &GlContext,
+        program: &Rc<GlProgram>,
+        view_projection: &Rc<Cell<Mat4>>,
+        counters: &Rc<Cell<RenderCounters>>,
         vertices: &[f32],
-    ) -> Result<Self, String> {
-        const VERTEX_LEN: i32 = 2;
-
+    ) -> Result<Self, Error> {
         if vertices.len() % 3 != 0
-            || (vertices.len() as i32 / 3) % VERTEX_LEN != 0
+            || (vertices.len() as i32 / 3) % I::VERTEX_LEN != 0
             || vertices.len() < 3
         {
-            Err(format!(
+            Err(Error::Invalid(format!(
                 "Expected [3 vertices with {} coordinates] for each triangle but found {} coordinates",
-                VERTEX_LEN,
+                I::VERTEX_LEN,
                 vertices.len()
-            ))
+            )))
         } else {
             let vertices_buffer = GlBuffer::new_static(&context, vertices)?;
             let instances_buffer = GlBuffer::new(context)?;
 
-            let vao = GlVAO::new(context)?;
-            vao.link_buffer(&vertices_buffer, &[Attrib::VecF32(0, 2)]);
-            vao.link_instance_buffer(
-                &instances_buffer,
-                &[
-                    Attrib::VecF32(1, 3),
-                    Attrib::VecF32(2, 4),
-                    Attrib::MatF32(3, 2, 2),
-                ],
-            );
-
-            Ok(Self {
-                vao,
+            let axes = I::VERTEX_LEN as usize;
+            let mut bounds = [[0.; 3]; 2];
+            bounds[0][..axes].fill(f32::MAX);
+            bounds[1][..axes].fill(f32::MIN);
+            for vertex in vertices.chunks_exact(axes) {
+                for (axis, &coord) in vertex.iter().enumerate() {
+                    bounds[0][axis] = bounds[0][axis].min(coord);
+                    bounds[1][axis] = bounds[1][axis].max(coord);
+                }
+            }
+
+            let mesh = Self {
+                vao: GlVAO::new(context)?,
                 vertices_buffer,
-                vertices_count: vertices.len() as i32 / VERTEX_LEN,
+                vertices_count: vertices.len() as i32 / I::VERTEX_LEN,
                 instances_data: vec![],
                 instances_buffer,
                 context: context.clone(),
+                program: program.clone(),
                 instances_count: 0,
-            })
+                view_projection: view_projection.clone(),
+                counters: counters.clone(),
+                bounds,
+                culling: true,
+                stats: CullStats::default(),
+                instance: PhantomData,
+            };
+            mesh.link();
+            Ok(mesh)
+        }
+    }
+
+    fn link(&self) {
+        self.vao
+            .link_buffer(&self.vertices_buffer, &[Attrib::VecF32(0, I::VERTEX_LEN)]);
+        self.vao
+            .link_instance_buffer(&self.instances_buffer, I::ATTRIBUTES);
+    }
+}
+
+/// Keeps the instances of `instances_data` whose `bounds` may be visible, in order
+fn cull_instances<I: Instance>(
+    instances_data: &mut Vec<f32>,
+    view_projection: &Mat4,
+    bounds: &[[f32; 3]; 2],
+) -> CullStats {
+    let len = I::DATA_LEN;
+    let count = instances_data.len() / len;
+    let mut drawn = 0;
+    for i in 0..count {
+        let start = i * len;
+        let model = I::model_matrix(&instances_data[start..start + len]);
+        if in_clip_space(&mat4_mul(view_projection, &model), bounds) {
+            instances_data.copy_within(start..start + len, drawn * len);
+            drawn += 1;
+        }
+    }
+    instances_data.truncate(drawn * len);
+
+    CullStats {
+        drawn: drawn as u32,
+        culled: (count - drawn) as u32,
+    }
+}
+
+/// Whether part of the box may be visible, i.e. not all its corners are
+/// outside of the same clip plane once transformed by `matrix`
+fn in_clip_space(matrix: &Mat4, bounds: &[[f32; 3]; 2]) -> bool {
+    let mut outside = [true; 6];
+    for corner in 0..8 {
+        let [x, y, z] = [
+            bounds[corner & 1][0],
+            bounds[(corner >> 1) & 1][1],
+            bounds[(corner >> 2) & 1][2],
+        ];
+        let clip = |row: usize| {
+            matrix[row] * x + matrix[4 + row] * y + matrix[8 + row] * z + matrix[12 + row]
+        };
+        let (cx, cy, cz, w) = (clip(0), clip(1), clip(2), clip(3));
+        for (plane, out) in [cx < -w, cx > w, cy < -w, cy > w, cz < -w, cz > w]
+            .into_iter()
+            .enumerate()
+        {
+            outside[plane] &= out;
         }
     }
+    !outside.contains(&true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT_BOX: [[f32; 3]; 2] = [[-1., -1., -1.], [1., 1., 1.]];
+
+    /// Camera at z = 5 looking at the origin, seeing 5 units on each side of it
+    fn view_projection() -> Mat4 {
+        mat4_mul(
+            &mat4_perspective(std::f32::consts::FRAC_PI_2, 1., 1., 100.),
+            &mat4_look_at([0., 0., 5.], [0., 0., 0.], [0., 1., 0.]),
+        )
+    }
+
+    fn instances(translations: &[[f32; 3]]) -> Vec<f32> {
+        translations
+            .iter()
+            .flat_map(|translation| InstanceProperties3D::new().translate(translation).0)
+            .collect()
+    }
+
+    #[test]
+    fn clip_space_box_checks_against_each_plane() {
+        assert!(in_clip_space(&IDENTITY_MAT4, &[[-0.5; 3], [0.5; 3]]));
+        assert!(in_clip_space(&IDENTITY_MAT4, &[[0.5; 3], [1.5; 3]]));
+        assert!(in_clip_space(&IDENTITY_MAT4, &[[-2.; 3], [2.; 3]]));
+        assert!(!in_clip_space(
+            &IDENTITY_MAT4,
+            &[[1.1, 0., 0.], [2., 0.5, 0.5]]
+        ));
+        assert!(!in_clip_space(
+            &IDENTITY_MAT4,
+            &[[0., 0., -3.], [0.5, 0.5, -1.1]]
+        ));
+    }
+
+    #[test]
+    fn keeps_boxes_inside_or_straddling_the_view() {
+        let mut data = instances(&[[0., 0., 0.], [5., 0., 0.], [0., -5., -2.]]);
+        let expected = data.clone();
+        let stats =
+            cull_instances::<InstanceProperties3D>(&mut data, &view_projection(), &UNIT_BOX);
+        assert_eq!(
+            stats,
+            CullStats {
+                drawn: 3,
+                culled: 0
+            }
+        );
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn culls_boxes_outside_of_the_view() {
+        let mut data = instances(&[
+            [20., 0., 0.],
+            [0., 0., 0.],
+            [0., 0., 10.],
+            [0., 0., -200.],
+            [0., 4., 0.],
+        ]);
+        let stats =
+            cull_instances::<InstanceProperties3D>(&mut data, &view_projection(), &UNIT_BOX);
+        assert_eq!(
+            stats,
+            CullStats {
+                drawn: 2,
+                culled: 3
+            }
+        );
+        assert_eq!(data, instances(&[[0., 0., 0.], [0., 4., 0.]]));
+    }
+
+    #[test]
+    fn culls_boxes_behind_the_camera() {
+        let mut data = instances(&[[0., 0., 7.], [3., 3., 12.]]);
+        let stats =
+            cull_instances::<InstanceProperties3D>(&mut data, &view_projection(), &UNIT_BOX);
+        assert_eq!(
+            stats,
+            CullStats {
+                drawn: 0,
+                culled: 2
+            }
+        );
+        assert!(data.is_empty());
+    }
 }
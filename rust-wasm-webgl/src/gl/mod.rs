@@ -1,64 +1,646 @@
+mod atlas;
 mod buffer;
+mod camera;
+mod canvas;
+mod capture;
+mod context;
+mod framebuffer;
+mod hud;
+mod math;
 mod mesh;
+mod picker;
+mod post;
 mod program;
+mod render_target;
+mod scene;
 mod shaders;
+mod sprite;
+mod text;
+mod texture;
 mod vao;
-use crate::error;
+use crate::error::Error;
+pub use atlas::*;
+use buffer::GlBuffer;
+pub use camera::*;
+use canvas::Canvas;
+pub use canvas::*;
+pub use capture::*;
+pub use context::ContextStatus;
+use context::{ContextListener, GlContext};
+pub use framebuffer::*;
+pub use hud::*;
+pub use math::*;
 pub use mesh::*;
+pub use picker::*;
+pub use post::*;
 pub use program::*;
-use wasm_bindgen::JsCast;
+use render_target::RenderTarget;
+use std::{cell::Cell, rc::Rc};
+use vao::GlVAO;
+// Not used by the demo yet
+#[allow(unused_imports)]
+pub use scene::*;
+pub use sprite::*;
+pub use text::*;
+pub use texture::*;
 use web_sys::*;
 
+/// Floats of the `Camera` uniform block: the view projection matrix, `pixel_snap`
+/// and `viewport_size` aligned to 8 bytes as required by std140
+const CAMERA_DATA_LEN: usize = 20;
+
+const MIN_RENDER_SCALE: f32 = 0.5;
+const MAX_RENDER_SCALE: f32 = 2.;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GlOptions {
+    /// MSAA samples, 0 disables it. Clamped to the maximum supported.
+    pub samples: i32,
+    /// Resolution of the rendering relative to the canvas, the frame is stretched
+    /// to the canvas on `Gl::present`. Clamped from 0.5 to 2.
+    pub render_scale: f32,
+    /// How `Gl::update_size` sizes the canvas
+    pub resize: CanvasResize,
+    pub context: ContextOptions,
+}
+
+impl Default for GlOptions {
+    fn default() -> Self {
+        Self {
+            samples: 0,
+            render_scale: 1.,
+            resize: CanvasResize::Window,
+            context: ContextOptions::default(),
+        }
+    }
+}
+
+/// Size of the canvas drawing buffer and of the area it covers on the page
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasSize {
+    pub width: i32,
+    pub height: i32,
+    pub css_width: f32,
+    pub css_height: f32,
+    pub device_pixel_ratio: f32,
+}
+
+/// Work of the GPU in one frame, counted between two `Gl::present`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderCounters {
+    pub draw_calls: u32,
+    pub instances: u32,
+    pub uploaded_bytes: u32,
+}
+
+impl RenderCounters {
+    pub(super) fn add(counters: &Cell<Self>, draw_calls: u32, instances: i32, uploaded: &[f32]) {
+        let mut sum = counters.get();
+        sum.draw_calls += draw_calls;
+        sum.instances += instances.max(0) as u32;
+        sum.uploaded_bytes += std::mem::size_of_val(uploaded) as u32;
+        counters.set(sum);
+    }
+}
+
 pub struct Gl {
-    context: WebGl2RenderingContext,
-    program: GlProgram,
+    context: GlContext,
+    canvas: Canvas,
+    program: Rc<GlProgram>,
+    program_3d: Rc<GlProgram>,
+    /// Stretches the offscreen target over the canvas
+    present_program: GlProgram,
+    present_vao: GlVAO,
+    camera_buffer: GlBuffer,
+    /// Kept to upload it again after a context loss
+    camera_data: [f32; CAMERA_DATA_LEN],
+    /// Shared with the meshes to cull their instances
+    view_projection: Rc<Cell<Mat4>>,
+    /// Shared with everything drawing, reset by `present`
+    counters: Rc<Cell<RenderCounters>>,
+    last_counters: RenderCounters,
+    context_listener: ContextListener,
+    options: GlOptions,
+    render_target: Option<RenderTarget>,
+    size: CanvasSize,
+    /// Letterboxed part of the canvas set by `set_pixel_camera`
+    viewport: Option<[i32; 4]>,
 }
 
 impl Gl {
-    pub fn init() -> Result<Gl, String> {
-        let canvas = load_canvas()?;
+    /// Renders to the `#canvas` element
+    pub fn init() -> Result<Gl, Error> {
+        Self::with_options(GlOptions::default())
+    }
+
+    pub fn with_options(options: GlOptions) -> Result<Gl, Error> {
+        Self::new(CanvasTarget::Id(String::from("canvas")), options)
+    }
+
+    /// Each `Gl` has its own context, several can render to different canvases of a page
+    pub fn new(target: CanvasTarget, options: GlOptions) -> Result<Gl, Error> {
+        let canvas =
+            Canvas::load(target).map_err(|e| e.context("Couldn't find the canvas to render to"))?;
         let context = canvas
-            .get_context("webgl2")
-            .map_err(|e| error::fmt(e, ""))?
-            .ok_or("defew")?
-            .dyn_into::<WebGl2RenderingContext>()
-            .map_err(|e| error::fmt(e, ""))?;
+            .context(&options.context)
+            .map_err(|e| e.context("Couldn't get a WebGL2 context from the canvas"))?;
+        Self::setup(canvas, GlContext::new(context), options)
+            .map_err(|e| e.context("Couldn't set up the WebGL2 context"))
+    }
+
+    fn setup(canvas: Canvas, context: GlContext, options: GlOptions) -> Result<Gl, Error> {
         context.enable(WebGl2RenderingContext::DEPTH_TEST);
-        
+
         let program = GlProgram::new(&context, shaders::VERTEX_SOURCE, shaders::FRAGMENT_SOURCE)?;
         program.bind();
+        let program_3d = GlProgram::new(
+            &context,
+            shaders::VERTEX_3D_SOURCE,
+            shaders::FRAGMENT_3D_SOURCE,
+        )?;
+
+        let present_program = GlProgram::new(
+            &context,
+            shaders::FULLSCREEN_VERTEX_SOURCE,
+            shaders::COPY_FRAGMENT_SOURCE,
+        )?;
+        let present_vao = GlVAO::new(&context)?;
 
-        Ok(Self {
-            program, 
+        let mut camera_buffer = GlBuffer::new_uniform(&context)?;
+        let mut camera_data = [0.; CAMERA_DATA_LEN];
+        camera_data[..16].copy_from_slice(&IDENTITY_MAT4);
+        camera_buffer.update(&camera_data);
+        camera_buffer.bind_base(program::CAMERA_BINDING);
+
+        let mut gl = Self {
+            program: Rc::new(program),
+            program_3d: Rc::new(program_3d),
+            present_program,
+            present_vao,
+            camera_buffer,
+            camera_data,
+            view_projection: Rc::new(Cell::new(IDENTITY_MAT4)),
+            counters: Rc::default(),
+            last_counters: RenderCounters::default(),
+            context_listener: ContextListener::new(canvas.event_target())?,
             context,
+            canvas: canvas.clone(),
+            options,
+            render_target: None,
+            size: CanvasSize {
+                width: canvas.width() as i32,
+                height: canvas.height() as i32,
+                css_width: canvas.width() as f32,
+                css_height: canvas.height() as f32,
+                device_pixel_ratio: 1.,
+            },
+            viewport: None,
+        };
+        gl.update_size()?;
+        gl.set_options(options)?;
+        Ok(gl)
+    }
+
+    pub fn options(&self) -> GlOptions {
+        self.options
+    }
+
+    pub fn set_render_scale(&mut self, render_scale: f32) -> Result<(), Error> {
+        self.set_options(GlOptions {
+            render_scale,
+            ..self.options
         })
     }
 
-    pub fn create_mesh(&self, vertices: &[f32]) -> Result<GlMesh, String> {
-        GlMesh::from_vertices(&self.context, vertices)
+    pub fn set_samples(&mut self, samples: i32) -> Result<(), Error> {
+        self.set_options(GlOptions {
+            samples,
+            ..self.options
+        })
+    }
+
+    fn set_options(&mut self, options: GlOptions) -> Result<(), Error> {
+        if options.render_scale.is_nan() {
+            return Err(Error::Invalid(format!(
+                "Invalid render scale {}",
+                options.render_scale
+            )));
+        }
+        let max_samples = self
+            .context
+            .get_parameter(WebGl2RenderingContext::MAX_SAMPLES)
+            .map_err(Error::js("Couldn't get MAX_SAMPLES"))?
+            .as_f64()
+            .unwrap_or(0.) as i32;
+
+        self.options = GlOptions {
+            samples: options.samples.clamp(0, max_samples),
+            render_scale: options
+                .render_scale
+                .clamp(MIN_RENDER_SCALE, MAX_RENDER_SCALE),
+            ..options
+        };
+        self.create_render_target()
+    }
+
+    fn create_render_target(&mut self) -> Result<(), Error> {
+        self.render_target = None;
+        if self.options.samples > 0 || self.options.render_scale != 1. {
+            self.render_target = Some(RenderTarget::new(
+                &self.context,
+                self.options.samples,
+                self.options.render_scale,
+                self.canvas_size(),
+            )?);
+        }
+        self.bind_canvas();
+        self.update_camera_buffer();
+        Ok(())
+    }
+
+    /// Transform applied to every instance position by the default, 3D, sprite and text shaders
+    pub fn set_view_projection(&mut self, matrix: &Mat4) {
+        self.set_camera_data(matrix, false, None);
+    }
+
+    pub fn set_camera_2d(&mut self, camera: &Camera2D) {
+        self.set_view_projection(&camera.view_projection(self.canvas_size()));
+    }
+
+    pub fn set_camera_3d(&mut self, camera: &Camera3D) {
+        self.set_view_projection(&camera.view_projection(self.canvas_size()));
+    }
+
+    /// Draws in pixels from the top left corner. With a virtual resolution
+    /// the canvas is rebound with the letterboxed viewport.
+    pub fn set_pixel_camera(&mut self, camera: &PixelCamera) -> PixelLayout {
+        let layout = self.pixel_layout(camera);
+        let viewport = match camera.resolution {
+            PixelResolution::Virtual(..) => Some(layout.viewport),
+            _ => None,
+        };
+        self.set_camera_data(&camera.view_projection(&layout), camera.snap, viewport);
+        layout
+    }
+
+    pub fn pixel_layout(&self, camera: &PixelCamera) -> PixelLayout {
+        camera.layout(self.canvas_size(), self.css_pixel_ratio())
+    }
+
+    /// Canvas pixels per CSS pixel
+    pub fn css_pixel_ratio(&self) -> f32 {
+        if self.size.css_width > 0. {
+            self.size.width as f32 / self.size.css_width
+        } else {
+            1.
+        }
+    }
+
+    fn set_camera_data(&mut self, matrix: &Mat4, snap: bool, viewport: Option<[i32; 4]>) {
+        self.camera_data[..16].copy_from_slice(matrix);
+        self.view_projection.set(*matrix);
+        self.camera_data[16] = if snap { 1. } else { 0. };
+        if self.viewport != viewport {
+            self.viewport = viewport;
+            self.bind_canvas();
+        }
+        self.update_camera_buffer();
+    }
+
+    /// Uploads the camera data with the size of the current viewport, which snapping needs
+    fn update_camera_buffer(&mut self) {
+        let [_, _, width, height] = self.render_viewport();
+        self.camera_data[18] = width as f32;
+        self.camera_data[19] = height as f32;
+        self.camera_buffer.update_slice(&self.camera_data, 0);
+        RenderCounters::add(&self.counters, 0, 0, &self.camera_data);
+    }
+
+    /// `screen` is in canvas pixels from its top left corner
+    pub fn screen_to_world(&self, camera: &Camera2D, screen: [f32; 2]) -> [f32; 2] {
+        camera.screen_to_world(screen, self.canvas_size())
+    }
+
+    pub fn world_to_screen(&self, camera: &Camera2D, world: [f32; 2]) -> [f32; 2] {
+        camera.world_to_screen(world, self.canvas_size())
+    }
+
+    /// Should be called at the start of every frame, nothing should be drawn while
+    /// the context is lost. When it's restored everything created from `Gl` is
+    /// rebuilt, the content of render targets is lost. `create_canvas_loop` calls it.
+    pub fn update_context(&mut self) -> Result<ContextStatus, Error> {
+        let status = self.context_listener.status();
+        if status == ContextStatus::Restored {
+            self.restore()
+                .map_err(|e| e.context("Couldn't restore the WebGL context"))?;
+            self.context_listener.set_ready();
+        }
+        Ok(status)
+    }
+
+    /// Calls `callback` once when the browser restores the lost context, e.g. to
+    /// resume a loop paused while it's lost
+    pub fn on_context_restored(&self, callback: impl FnOnce() + 'static) {
+        self.context_listener.on_restored(Box::new(callback));
+    }
+
+    /// The objects created from the context restore themselves, then the state of `Gl`
+    /// is set again
+    fn restore(&mut self) -> Result<(), Error> {
+        self.context.restore_resources()?;
+
+        self.context.enable(WebGl2RenderingContext::DEPTH_TEST);
+        self.camera_buffer.update(&self.camera_data);
+        self.camera_buffer.bind_base(program::CAMERA_BINDING);
+
+        self.set_options(self.options)
+    }
+
+    /// Resizes the canvas following `GlOptions::resize` at the device pixel ratio,
+    /// should be called at the start of every frame, see `create_canvas_loop`
+    pub fn update_size(&mut self) -> Result<CanvasSize, Error> {
+        let canvas = match (self.options.resize, self.canvas.element()) {
+            (CanvasResize::Window | CanvasResize::Css, Some(canvas)) => canvas.clone(),
+            _ => return Ok(self.size),
+        };
+        let window =
+            web_sys::window().ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?;
+        let device_pixel_ratio = window.device_pixel_ratio() as f32;
+
+        let (css_width, css_height) = if self.options.resize == CanvasResize::Window {
+            let width = window
+                .inner_width()
+                .map_err(Error::js("Couldn't get window width"))?
+                .as_f64()
+                .ok_or_else(|| Error::Dom(String::from("Invalid window width")))?;
+            let height = window
+                .inner_height()
+                .map_err(Error::js("Couldn't get window height"))?
+                .as_f64()
+                .ok_or_else(|| Error::Dom(String::from("Invalid window height")))?;
+            (width as f32, height as f32)
+        } else {
+            (canvas.client_width() as f32, canvas.client_height() as f32)
+        };
+
+        let size = CanvasSize {
+            width: ((css_width * device_pixel_ratio).round() as i32).max(1),
+            height: ((css_height * device_pixel_ratio).round() as i32).max(1),
+            css_width,
+            css_height,
+            device_pixel_ratio,
+        };
+        if size == self.size {
+            return Ok(size);
+        }
+
+        if self.options.resize == CanvasResize::Window {
+            let style = canvas.style();
+            style
+                .set_property("width", &format!("{}px", css_width))
+                .map_err(Error::js("Couldn't set canvas width"))?;
+            style
+                .set_property("height", &format!("{}px", css_height))
+                .map_err(Error::js("Couldn't set canvas height"))?;
+        }
+        let previous = self.size;
+        self.resize(size)?;
+
+        // Without a CSS size the canvas is as large as its drawing buffer, and
+        // would grow on every frame above a pixel ratio of 1
+        if self.options.resize == CanvasResize::Css
+            && ((canvas.client_width() as f32 - css_width).abs() > 1.
+                || (canvas.client_height() as f32 - css_height).abs() > 1.)
+        {
+            self.options.resize = CanvasResize::Manual;
+            self.resize(previous)?;
+            return Err(Error::Invalid(String::from(
+                "CanvasResize::Css needs a CSS width and height on the canvas, \
+                 switched to CanvasResize::Manual",
+            )));
+        }
+        Ok(size)
+    }
+
+    /// Sets the size of the drawing buffer, for `CanvasResize::Manual`
+    pub fn set_size(&mut self, width: i32, height: i32) -> Result<(), Error> {
+        let device_pixel_ratio = self.size.device_pixel_ratio;
+        self.resize(CanvasSize {
+            width: width.max(1),
+            height: height.max(1),
+            css_width: width.max(1) as f32 / device_pixel_ratio,
+            css_height: height.max(1) as f32 / device_pixel_ratio,
+            device_pixel_ratio,
+        })
+    }
+
+    fn resize(&mut self, size: CanvasSize) -> Result<(), Error> {
+        let resized = (size.width, size.height) != (self.size.width, self.size.height);
+        self.size = size;
+        if resized {
+            self.canvas.set_size(size.width as u32, size.height as u32);
+            self.create_render_target()?;
+        }
+        Ok(())
+    }
+
+    /// `None` for offscreen canvases
+    pub fn canvas_element(&self) -> Option<&HtmlCanvasElement> {
+        self.canvas.element()
+    }
+
+    pub fn size(&self) -> CanvasSize {
+        self.size
+    }
+
+    pub fn canvas_size(&self) -> (i32, i32) {
+        (self.canvas.width() as i32, self.canvas.height() as i32)
+    }
+
+    /// Size of the buffer the draws end in, the canvas scaled by `GlOptions::render_scale`
+    pub fn render_size(&self) -> (i32, i32) {
+        match &self.render_target {
+            Some(target) => target.size(),
+            None => self.canvas_size(),
+        }
+    }
+
+    /// Shows the frame drawn since `clear_canvas` when drawing through an
+    /// offscreen target (MSAA or render scale), and ends the frame of `counters`
+    pub fn present(&mut self) -> Result<(), Error> {
+        self.last_counters = self.counters.take();
+        let canvas_size = self.canvas_size();
+        if let Some(target) = &self.render_target {
+            // Stretched over the canvas even if it was resized during the frame
+            target
+                .present(canvas_size, &self.present_program, &self.present_vao)
+                .map_err(|e| e.context("Couldn't present the render target"))?;
+            if !target.fits(canvas_size) {
+                self.create_render_target()
+                    .map_err(|e| e.context("Couldn't resize the render target"))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the canvas, call it after `present` in the same frame unless the
+    /// context preserves its drawing buffer
+    pub fn capture(&self) -> Result<Capture, Error> {
+        Capture::read(self).map_err(|e| e.context("Couldn't capture the canvas"))
+    }
+
+    /// Counts of the last presented frame
+    pub fn counters(&self) -> RenderCounters {
+        self.last_counters
+    }
+
+    pub fn create_mesh(&self, vertices: &[f32]) -> Result<GlMesh, Error> {
+        GlMesh::from_vertices(
+            &self.context,
+            &self.program,
+            &self.view_projection,
+            &self.counters,
+            vertices,
+        )
+        .map_err(|e| e.context("Couldn't create the mesh"))
+    }
+
+    /// `vertices` has 3 coordinates per vertex, instances are placed by their model matrix
+    pub fn create_mesh_3d(&self, vertices: &[f32]) -> Result<GlMesh<InstanceProperties3D>, Error> {
+        GlMesh::from_vertices(
+            &self.context,
+            &self.program_3d,
+            &self.view_projection,
+            &self.counters,
+            vertices,
+        )
+        .map_err(|e| e.context("Couldn't create the 3D mesh"))
+    }
+
+    pub fn create_texture(
+        &self,
+        format: TextureFormat,
+        width: i32,
+        height: i32,
+    ) -> Result<GlTexture, Error> {
+        GlTexture::new_2d(&self.context, format, width, height)
+            .map_err(|e| e.context("Couldn't create the texture"))
+    }
+
+    pub fn create_texture_array(
+        &self,
+        format: TextureFormat,
+        width: i32,
+        height: i32,
+        layers: i32,
+    ) -> Result<GlTexture, Error> {
+        GlTexture::new_2d_array(&self.context, format, width, height, layers)
+            .map_err(|e| e.context("Couldn't create the texture array"))
+    }
+
+    pub fn create_texture_from_image(
+        &self,
+        format: TextureFormat,
+        image: &HtmlImageElement,
+    ) -> Result<GlTexture, Error> {
+        GlTexture::from_image(&self.context, format, image)
+            .map_err(|e| e.context("Couldn't create the texture of the image"))
+    }
+
+    /// Each atlas page is a `page_size` square layer of the atlas texture array
+    pub fn create_sprite_batch(&self, page_size: i32) -> Result<SpriteBatch, Error> {
+        SpriteBatch::new(&self.context, &self.counters, page_size)
+            .map_err(|e| e.context("Couldn't create the sprite batch"))
+    }
+
+    pub fn create_text_renderer(&self, font: crate::text::Font) -> Result<TextRenderer, Error> {
+        TextRenderer::new(&self.context, &self.counters, font)
+            .map_err(|e| e.context("Couldn't create the text renderer"))
+    }
+
+    pub fn create_framebuffer(
+        &self,
+        options: FramebufferOptions,
+        width: i32,
+        height: i32,
+    ) -> Result<GlFramebuffer, Error> {
+        GlFramebuffer::new(&self.context, options, width, height)
+            .map_err(|e| e.context("Couldn't create the framebuffer"))
+    }
+
+    /// Use a float `format` (`Rgba16F`) for HDR effects like bloom and tone mapping
+    pub fn create_post_process(&self, format: TextureFormat) -> Result<PostProcess, Error> {
+        PostProcess::new(&self.context, &self.canvas, format)
+            .map_err(|e| e.context("Couldn't create the post process"))
+    }
+
+    pub fn create_picker(&self) -> Result<Picker, Error> {
+        Picker::new(&self.context, &self.canvas)
+            .map_err(|e| e.context("Couldn't create the picker"))
+    }
+
+    pub fn create_hud(&self) -> Result<Hud, Error> {
+        Hud::new(self).map_err(|e| e.context("Couldn't create the HUD"))
+    }
+
+    /// Runs `draw` with `framebuffer` as the render target and then goes back to the canvas
+    pub fn draw_to<F>(&self, framebuffer: &GlFramebuffer, draw: F) -> Result<(), Error>
+    where
+        F: FnOnce() -> Result<(), Error>,
+    {
+        framebuffer.bind();
+        let result = draw();
+        self.bind_canvas();
+        result
+    }
+
+    /// Binds the canvas, or the offscreen target that ends in the canvas when there is one
+    pub fn bind_canvas(&self) {
+        match &self.render_target {
+            Some(target) => target.bind(),
+            None => self
+                .context
+                .bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None),
+        }
+        let [x, y, width, height] = self.render_viewport();
+        self.context.viewport(x, y, width, height);
+    }
+
+    /// Viewport of `bind_canvas` in canvas pixels, letterboxed by a virtual `PixelCamera`
+    pub(super) fn canvas_viewport(&self) -> [i32; 4] {
+        let (width, height) = self.canvas_size();
+        self.viewport.unwrap_or([0, 0, width, height])
+    }
+
+    /// Viewport of `bind_canvas` in pixels of the render target
+    fn render_viewport(&self) -> [i32; 4] {
+        let (render_width, render_height) = self.render_size();
+        match self.viewport {
+            Some([x, y, width, height]) => {
+                let (canvas_width, canvas_height) = self.canvas_size();
+                let scale_x = render_width as f32 / canvas_width.max(1) as f32;
+                let scale_y = render_height as f32 / canvas_height.max(1) as f32;
+                [
+                    (x as f32 * scale_x).round() as i32,
+                    (y as f32 * scale_y).round() as i32,
+                    (width as f32 * scale_x).round() as i32,
+                    (height as f32 * scale_y).round() as i32,
+                ]
+            }
+            None => [0, 0, render_width, render_height],
+        }
+    }
+
+    /// Binds `texture` to the texture `unit` and points the sampler uniform `sampler` to it
+    pub fn bind_texture(&self, sampler: &str, texture: &GlTexture, unit: u32) {
+        self.program.bind_texture(sampler, texture, unit);
     }
 
     pub fn clear_canvas(&self, color: &[f32; 4]) {
+        self.bind_canvas();
         self.context
             .clear_color(color[0], color[1], color[2], color[3]);
         self.context.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
         self.context.clear(WebGl2RenderingContext::DEPTH_BUFFER_BIT);
     }
 }
-
-fn load_canvas() -> Result<web_sys::HtmlCanvasElement, String> {
-    let window = web_sys::window().ok_or("Couldn't get window")?;
-    let document = window.document().ok_or("Couldn't get document")?;
-
-    let canvas = document
-        .get_element_by_id("canvas")
-        .ok_or("Couldn't get canvas")?;
-    let canvas: web_sys::HtmlCanvasElement = canvas
-        .dyn_into::<web_sys::HtmlCanvasElement>()
-        .map_err(|e| error::fmt(e, "Invalid canvas"))?;
-
-    canvas.set_width(window.inner_width().unwrap().as_f64().unwrap() as u32);
-    canvas.set_height(window.inner_height().unwrap().as_f64().unwrap() as u32);
-
-    Ok(canvas)
-}
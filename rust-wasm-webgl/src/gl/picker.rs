@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+
+use super::{canvas::Canvas, program::Uniform, *};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PickId {
+    /// Id given to `Picker::draw`
+    pub mesh: u32,
+    /// Index of the instance in the order they were created
+    pub instance: u32,
+}
+
+/// Finds the instances under canvas positions by drawing their ids into an
+/// integer target the size of the canvas.
+///
+/// ```ignore
+/// picker.begin(&gl)?;
+/// picker.draw(&mut mesh, 1)?;
+/// let picked = picker.pick(input.pointer_position())?;
+/// gl.bind_canvas();
+/// mesh.draw();
+/// ```
+pub struct Picker {
+    context: GlContext,
+    canvas: Canvas,
+    framebuffer: GlFramebuffer,
+    /// Viewport of the canvas when `begin` was called
+    viewport: [i32; 4],
+    /// Programs by vertex shader, compiled when a mesh kind is first drawn
+    programs: Vec<(&'static str, GlProgram)>,
+}
+
+impl Picker {
+    pub(super) fn new(context: &GlContext, canvas: &Canvas) -> Result<Self, Error> {
+        Ok(Self {
+            context: context.clone(),
+            canvas: canvas.clone(),
+            framebuffer: GlFramebuffer::new(
+                context,
+                FramebufferOptions {
+                    color: Attachment::Renderbuffer(TextureFormat::Rg32UI),
+                    depth: Attachment::Renderbuffer(TextureFormat::Depth24),
+                    samples: 0,
+                },
+                canvas.width().max(1) as i32,
+                canvas.height().max(1) as i32,
+            )?,
+            viewport: [0, 0, canvas.width() as i32, canvas.height() as i32],
+            programs: vec![],
+        })
+    }
+
+    /// Binds and clears the id target, resizing it to the canvas if needed.
+    /// The ids are drawn with the current viewport of `gl`.
+    pub fn begin(&mut self, gl: &Gl) -> Result<(), Error> {
+        self.viewport = gl.canvas_viewport();
+        self.framebuffer.resize(
+            self.canvas.width().max(1) as i32,
+            self.canvas.height().max(1) as i32,
+        )?;
+        self.framebuffer.clear(&[0., 0., 0., 0.]);
+        Ok(())
+    }
+
+    /// Draws the ids of the instances created since the last `GlMesh::draw`,
+    /// which are kept for it. `mesh_id` must not be 0.
+    pub fn draw<I: Instance>(&mut self, mesh: &mut GlMesh<I>, mesh_id: u32) -> Result<(), Error> {
+        if mesh_id == 0 {
+            return Err(Error::Invalid(String::from(
+                "Mesh id 0 is reserved for the background",
+            )));
+        }
+
+        self.framebuffer.bind();
+        let [x, y, width, height] = self.viewport;
+        self.context.viewport(x, y, width, height);
+        let program = self.program(I::PICK_VERTEX_SOURCE)?;
+        program.bind();
+        program.set_uniform("mesh_id", Uniform::Vec1U32(&[mesh_id]));
+        mesh.draw_with(program);
+        Ok(())
+    }
+
+    /// `position` is in canvas pixels from its top left corner
+    pub fn pick(&self, position: [f32; 2]) -> Result<Option<PickId>, Error> {
+        let (x, y) = (position[0].floor() as i32, position[1].floor() as i32);
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        if x < 0 || y < 0 || x >= width || y >= height {
+            return Ok(None);
+        }
+
+        let pixel = self.framebuffer.read_pixels_u32(x, height - 1 - y, 1, 1)?;
+        Ok(pick_id(&pixel))
+    }
+
+    /// Every instance visible in the rectangle between the `min` and `max` corners
+    pub fn pick_rect(&self, min: [f32; 2], max: [f32; 2]) -> Result<Vec<PickId>, Error> {
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        let x0 = (min[0].min(max[0]).floor() as i32).clamp(0, width);
+        let x1 = (min[0].max(max[0]).ceil() as i32).clamp(0, width);
+        let y0 = (min[1].min(max[1]).floor() as i32).clamp(0, height);
+        let y1 = (min[1].max(max[1]).ceil() as i32).clamp(0, height);
+        if x0 == x1 || y0 == y1 {
+            return Ok(vec![]);
+        }
+
+        let pixels = self
+            .framebuffer
+            .read_pixels_u32(x0, height - y1, x1 - x0, y1 - y0)?;
+        let mut seen = HashSet::new();
+        Ok(pixels
+            .chunks_exact(4)
+            .filter_map(pick_id)
+            .filter(|id| seen.insert(*id))
+            .collect())
+    }
+
+    fn program(&mut self, vertex_shader_src: &'static str) -> Result<&GlProgram, Error> {
+        let index = match self
+            .programs
+            .iter()
+            .position(|(src, _)| *src == vertex_shader_src)
+        {
+            Some(index) => index,
+            None => {
+                let program = GlProgram::new(
+                    &self.context,
+                    vertex_shader_src,
+                    shaders::PICK_FRAGMENT_SOURCE,
+                )?;
+                self.programs.push((vertex_shader_src, program));
+                self.programs.len() - 1
+            }
+        };
+        Ok(&self.programs[index].1)
+    }
+}
+
+fn pick_id(pixel: &[u32]) -> Option<PickId> {
+    match pixel {
+        [mesh, instance, ..] if *mesh != 0 => Some(PickId {
+            mesh: *mesh,
+            instance: instance - 1,
+        }),
+        _ => None,
+    }
+}
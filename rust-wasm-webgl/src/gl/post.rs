@@ -0,0 +1,266 @@
+use std::rc::Rc;
+
+use super::{canvas::Canvas, program::Uniform, vao::GlVAO, *};
+
+#[derive(Clone)]
+pub enum Effect {
+    Bloom {
+        threshold: f32,
+        intensity: f32,
+        /// Blur radius in pixels
+        radius: f32,
+    },
+    Blur {
+        radius: f32,
+    },
+    Vignette {
+        intensity: f32,
+        smoothness: f32,
+    },
+    /// `lut` is a `size² x size` strip of `size` slices, see `COLOR_GRADING_FRAGMENT_SOURCE`
+    ColorGrading {
+        lut: Rc<GlTexture>,
+        intensity: f32,
+    },
+    Fxaa,
+    ToneMapping {
+        exposure: f32,
+        gamma: f32,
+    },
+}
+
+struct Programs {
+    copy: GlProgram,
+    threshold: GlProgram,
+    blur: GlProgram,
+    bloom: GlProgram,
+    vignette: GlProgram,
+    color_grading: GlProgram,
+    fxaa: GlProgram,
+    tone_mapping: GlProgram,
+}
+
+/// Renders the scene into an offscreen target and applies `effects` in order,
+/// the last pass drawing into the target of `Gl` (see `Gl::bind_canvas`).
+///
+/// ```ignore
+/// post.begin(&[0., 0., 0., 1.])?;
+/// mesh.draw();
+/// post.end(&gl)?;
+/// gl.present()?;
+/// ```
+pub struct PostProcess {
+    context: GlContext,
+    canvas: Canvas,
+    programs: Programs,
+    /// The scene target (with depth) followed by two ping-pong targets
+    targets: [GlFramebuffer; 3],
+    vao: GlVAO,
+    pub effects: Vec<Effect>,
+}
+
+impl PostProcess {
+    pub(super) fn new(
+        context: &GlContext,
+        canvas: &Canvas,
+        format: TextureFormat,
+    ) -> Result<Self, Error> {
+        let program = |fragment_shader_src| {
+            GlProgram::new(
+                context,
+                shaders::FULLSCREEN_VERTEX_SOURCE,
+                fragment_shader_src,
+            )
+        };
+        let programs = Programs {
+            copy: program(shaders::COPY_FRAGMENT_SOURCE)?,
+            threshold: program(shaders::THRESHOLD_FRAGMENT_SOURCE)?,
+            blur: program(shaders::BLUR_FRAGMENT_SOURCE)?,
+            bloom: program(shaders::BLOOM_FRAGMENT_SOURCE)?,
+            vignette: program(shaders::VIGNETTE_FRAGMENT_SOURCE)?,
+            color_grading: program(shaders::COLOR_GRADING_FRAGMENT_SOURCE)?,
+            fxaa: program(shaders::FXAA_FRAGMENT_SOURCE)?,
+            tone_mapping: program(shaders::TONE_MAPPING_FRAGMENT_SOURCE)?,
+        };
+
+        let (width, height) = (canvas.width().max(1) as i32, canvas.height().max(1) as i32);
+        let color_only = FramebufferOptions {
+            color: Attachment::Texture(format),
+            depth: Attachment::None,
+            samples: 0,
+        };
+        let targets = [
+            GlFramebuffer::new(
+                context,
+                FramebufferOptions {
+                    color: Attachment::Texture(format),
+                    depth: Attachment::Renderbuffer(TextureFormat::Depth24),
+                    samples: 0,
+                },
+                width,
+                height,
+            )?,
+            GlFramebuffer::new(context, color_only, width, height)?,
+            GlFramebuffer::new(context, color_only, width, height)?,
+        ];
+
+        Ok(Self {
+            context: context.clone(),
+            canvas: canvas.clone(),
+            programs,
+            targets,
+            vao: GlVAO::new(context)?,
+            effects: vec![],
+        })
+    }
+
+    /// Binds and clears the scene target, resizing the targets to the canvas if needed
+    pub fn begin(&mut self, clear_color: &[f32; 4]) -> Result<(), Error> {
+        let (width, height) = self.canvas_size();
+        for target in &mut self.targets {
+            target.resize(width, height)?;
+        }
+        self.targets[0].clear(clear_color);
+        Ok(())
+    }
+
+    /// Runs the effects and draws the result to the target of `gl`, which is left bound
+    pub fn end(&mut self, gl: &Gl) -> Result<(), Error> {
+        self.context.disable(WebGl2RenderingContext::DEPTH_TEST);
+        self.vao.bind();
+
+        let result = self.run_effects(gl);
+
+        self.context.enable(WebGl2RenderingContext::DEPTH_TEST);
+        gl.bind_canvas();
+        result
+    }
+
+    fn run_effects(&self, gl: &Gl) -> Result<(), Error> {
+        let mut source = 0;
+        let (width, height) = self.canvas_size();
+        let texel = [1. / width as f32, 1. / height as f32];
+
+        if self.effects.is_empty() {
+            return self.pass(gl, &self.programs.copy, source, None, |_| {});
+        }
+
+        for (i, effect) in self.effects.iter().enumerate() {
+            let last = i + 1 == self.effects.len();
+            let free: Vec<usize> = (0..3).filter(|&t| t != source).collect();
+            let output = if last { None } else { Some(free[0]) };
+
+            match effect {
+                Effect::Bloom {
+                    threshold,
+                    intensity,
+                    radius,
+                } => {
+                    self.pass(gl, &self.programs.threshold, source, Some(free[0]), |p| {
+                        p.set_uniform("threshold", Uniform::Vec1F32(&[*threshold]));
+                    })?;
+                    self.blur(gl, free[0], free[1], *radius, &texel)?;
+
+                    let bloom_texture = self.texture(free[0])?;
+                    let output = if last { None } else { Some(free[1]) };
+                    self.pass(gl, &self.programs.bloom, source, output, |p| {
+                        p.bind_texture("bloom", &bloom_texture, 1);
+                        p.set_uniform("intensity", Uniform::Vec1F32(&[*intensity]));
+                    })?;
+                    source = free[1];
+                    continue;
+                }
+                Effect::Blur { radius } => {
+                    self.blur(gl, source, free[0], *radius, &texel)?;
+                    if last {
+                        self.pass(gl, &self.programs.copy, source, None, |_| {})?;
+                    }
+                    continue;
+                }
+                Effect::Vignette {
+                    intensity,
+                    smoothness,
+                } => self.pass(gl, &self.programs.vignette, source, output, |p| {
+                    p.set_uniform("intensity", Uniform::Vec1F32(&[*intensity]));
+                    p.set_uniform("smoothness", Uniform::Vec1F32(&[*smoothness]));
+                })?,
+                Effect::ColorGrading { lut, intensity } => {
+                    self.pass(gl, &self.programs.color_grading, source, output, |p| {
+                        p.bind_texture("lut", lut, 1);
+                        p.set_uniform("lut_size", Uniform::Vec1F32(&[lut.height() as f32]));
+                        p.set_uniform("intensity", Uniform::Vec1F32(&[*intensity]));
+                    })?
+                }
+                Effect::Fxaa => self.pass(gl, &self.programs.fxaa, source, output, |p| {
+                    p.set_uniform("texel", Uniform::Vec2F32(&texel));
+                })?,
+                Effect::ToneMapping { exposure, gamma } => {
+                    self.pass(gl, &self.programs.tone_mapping, source, output, |p| {
+                        p.set_uniform("exposure", Uniform::Vec1F32(&[*exposure]));
+                        p.set_uniform("gamma", Uniform::Vec1F32(&[*gamma]));
+                    })?
+                }
+            }
+            source = free[0];
+        }
+        Ok(())
+    }
+
+    /// Blurs `target` in place using `temporary`
+    fn blur(
+        &self,
+        gl: &Gl,
+        target: usize,
+        temporary: usize,
+        radius: f32,
+        texel: &[f32; 2],
+    ) -> Result<(), Error> {
+        self.pass(gl, &self.programs.blur, target, Some(temporary), |p| {
+            p.set_uniform("direction", Uniform::Vec2F32(&[texel[0] * radius / 3., 0.]));
+        })?;
+        self.pass(gl, &self.programs.blur, temporary, Some(target), |p| {
+            p.set_uniform("direction", Uniform::Vec2F32(&[0., texel[1] * radius / 3.]));
+        })
+    }
+
+    /// `output` is one of the targets, or the target of `gl` for `None`
+    fn pass<F>(
+        &self,
+        gl: &Gl,
+        program: &GlProgram,
+        source: usize,
+        output: Option<usize>,
+        set_uniforms: F,
+    ) -> Result<(), Error>
+    where
+        F: FnOnce(&GlProgram),
+    {
+        match output {
+            Some(target) => self.targets[target].bind(),
+            None => {
+                // The whole target, the source already holds any letterboxing
+                gl.bind_canvas();
+                let (width, height) = gl.render_size();
+                self.context.viewport(0, 0, width, height);
+            }
+        }
+        program.bind_texture("source", &self.texture(source)?, 0);
+        set_uniforms(program);
+        self.context
+            .draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+        Ok(())
+    }
+
+    fn texture(&self, target: usize) -> Result<GlTexture, Error> {
+        self.targets[target].color_texture().ok_or_else(|| {
+            Error::Invalid(String::from("Post process target without color texture"))
+        })
+    }
+
+    fn canvas_size(&self) -> (i32, i32) {
+        (
+            self.canvas.width().max(1) as i32,
+            self.canvas.height().max(1) as i32,
+        )
+    }
+}
@@ -1,6 +1,9 @@
+use std::{cell::RefCell, rc::Rc};
+
 use web_sys::*;
 
-use crate::error;
+use super::{context::GlContext, texture::GlTexture};
+use crate::error::{Error, ShaderStage};
 
 #[derive(Debug)]
 pub(super) enum Uniform<'a> {
@@ -12,59 +15,112 @@ pub(super) enum Uniform<'a> {
     Vec2I32(&'a [i32]),
     Vec3I32(&'a [i32]),
     Vec4I32(&'a [i32]),
+    Vec1U32(&'a [u32]),
 }
 
 pub struct GlProgram {
-    gl_context: WebGl2RenderingContext,
-    pub(super) program: WebGlProgram,
+    shared: Rc<SharedProgram>,
+}
+
+struct SharedProgram {
+    gl_context: GlContext,
+    program: RefCell<WebGlProgram>,
+    /// Kept to rebuild the program after a context loss
+    vertex_shader_src: String,
+    fragment_shader_src: String,
 }
 
 const VERTEX_SHADER: u32 = WebGl2RenderingContext::VERTEX_SHADER;
 const FRAGMENT_SHADER: u32 = WebGl2RenderingContext::FRAGMENT_SHADER;
 
+/// Uniform block binding point of the `Camera` block shared by all programs
+pub(super) const CAMERA_BINDING: u32 = 0;
+
 impl GlProgram {
     pub(super) fn new(
-        gl: &WebGl2RenderingContext,
+        gl: &GlContext,
         vertex_shader_src: &str,
         fragment_shader_src: &str,
-    ) -> Result<GlProgram, String> {
-        let vertex_shader = compile_shader(gl, VERTEX_SHADER, vertex_shader_src)?;
-        let fragment_shader = compile_shader(gl, FRAGMENT_SHADER, fragment_shader_src)?;
-
-        Ok(GlProgram {
-            gl_context: gl.clone(),
-            program: link_shaders(gl, &vertex_shader, &fragment_shader)?,
-        })
+    ) -> Result<GlProgram, Error> {
+        let program = GlProgram {
+            shared: Rc::new(SharedProgram {
+                gl_context: gl.clone(),
+                program: RefCell::new(create_program(gl, vertex_shader_src, fragment_shader_src)?),
+                vertex_shader_src: String::from(vertex_shader_src),
+                fragment_shader_src: String::from(fragment_shader_src),
+            }),
+        };
+        gl.register(&program.shared, |shared| {
+            // Rebuilt from the sources after the context was restored
+            *shared.program.borrow_mut() = create_program(
+                &shared.gl_context,
+                &shared.vertex_shader_src,
+                &shared.fragment_shader_src,
+            )?;
+            Ok(())
+        });
+        Ok(program)
     }
 
-    fn set_uniform(&self, name: &str, value: Uniform) {
-        let location = self.gl_context.get_uniform_location(&self.program, name);
+    /// The program must be bound
+    pub(super) fn set_uniform(&self, name: &str, value: Uniform) {
+        let shared = &self.shared;
+        let location = shared
+            .gl_context
+            .get_uniform_location(&shared.program.borrow(), name);
         let loc = location.as_ref();
         match value {
-            Uniform::Vec1F32(data) => self.gl_context.uniform1fv_with_f32_array(loc, data),
-            Uniform::Vec2F32(data) => self.gl_context.uniform2fv_with_f32_array(loc, data),
-            Uniform::Vec3F32(data) => self.gl_context.uniform3fv_with_f32_array(loc, data),
-            Uniform::Vec4F32(data) => self.gl_context.uniform4fv_with_f32_array(loc, data),
-            Uniform::Vec1I32(data) => self.gl_context.uniform1iv_with_i32_array(loc, data),
-            Uniform::Vec2I32(data) => self.gl_context.uniform2iv_with_i32_array(loc, data),
-            Uniform::Vec3I32(data) => self.gl_context.uniform3iv_with_i32_array(loc, data),
-            Uniform::Vec4I32(data) => self.gl_context.uniform4iv_with_i32_array(loc, data),
+            Uniform::Vec1F32(data) => shared.gl_context.uniform1fv_with_f32_array(loc, data),
+            Uniform::Vec2F32(data) => shared.gl_context.uniform2fv_with_f32_array(loc, data),
+            Uniform::Vec3F32(data) => shared.gl_context.uniform3fv_with_f32_array(loc, data),
+            Uniform::Vec4F32(data) => shared.gl_context.uniform4fv_with_f32_array(loc, data),
+            Uniform::Vec1I32(data) => shared.gl_context.uniform1iv_with_i32_array(loc, data),
+            Uniform::Vec2I32(data) => shared.gl_context.uniform2iv_with_i32_array(loc, data),
+            Uniform::Vec3I32(data) => shared.gl_context.uniform3iv_with_i32_array(loc, data),
+            Uniform::Vec4I32(data) => shared.gl_context.uniform4iv_with_i32_array(loc, data),
+            Uniform::Vec1U32(data) => shared.gl_context.uniform1uiv_with_u32_array(loc, data),
         }
     }
 
+    pub(super) fn bind_texture(&self, sampler: &str, texture: &GlTexture, unit: u32) {
+        texture.bind_to_unit(unit);
+        self.bind();
+        self.set_uniform(sampler, Uniform::Vec1I32(&[unit as i32]));
+    }
+
     pub(super) fn bind(&self) {
-        self.gl_context.use_program(Some(&self.program));
+        let shared = &self.shared;
+        shared
+            .gl_context
+            .use_program(Some(&shared.program.borrow()));
+    }
+}
+
+fn create_program(
+    gl: &WebGl2RenderingContext,
+    vertex_shader_src: &str,
+    fragment_shader_src: &str,
+) -> Result<WebGlProgram, Error> {
+    let vertex_shader = compile_shader(gl, VERTEX_SHADER, vertex_shader_src)?;
+    let fragment_shader = compile_shader(gl, FRAGMENT_SHADER, fragment_shader_src)?;
+
+    let program = link_shaders(gl, &vertex_shader, &fragment_shader)?;
+
+    let camera_block = gl.get_uniform_block_index(&program, "Camera");
+    if camera_block != WebGl2RenderingContext::INVALID_INDEX {
+        gl.uniform_block_binding(&program, camera_block, CAMERA_BINDING);
     }
+    Ok(program)
 }
 
 fn link_shaders(
     context: &WebGl2RenderingContext,
     vertex_shader: &WebGlShader,
     fragment_shader: &WebGlShader,
-) -> Result<WebGlProgram, String> {
+) -> Result<WebGlProgram, Error> {
     let program = context
         .create_program()
-        .ok_or_else(|| String::from("Couldn't create shader object"))?;
+        .ok_or_else(|| Error::Allocation(String::from("Couldn't create program object")))?;
 
     context.attach_shader(&program, vertex_shader);
     context.attach_shader(&program, fragment_shader);
@@ -78,10 +134,11 @@ fn link_shaders(
         // context.use_program(Some(&program));
         Ok(program)
     } else {
-        Err(context
-            .get_program_info_log(&program)
-            .map(|e| error::fmt(e, "Couldn't link shaders"))
-            .unwrap_or_else(|| String::from("Unknown error creating program object")))
+        Err(Error::ShaderLink(
+            context
+                .get_program_info_log(&program)
+                .unwrap_or_else(|| String::from("Unknown error creating program object")),
+        ))
     }
 }
 
@@ -89,10 +146,10 @@ fn compile_shader(
     context: &WebGl2RenderingContext,
     shader_type: u32,
     source: &str,
-) -> Result<WebGlShader, String> {
+) -> Result<WebGlShader, Error> {
     let shader = context
         .create_shader(shader_type)
-        .ok_or_else(|| String::from("Couldn't create shader object"))?;
+        .ok_or_else(|| Error::Allocation(String::from("Couldn't create shader object")))?;
     context.shader_source(&shader, source);
     context.compile_shader(&shader);
 
@@ -103,25 +160,22 @@ fn compile_shader(
     {
         Ok(shader)
     } else {
-        let shader_name = if shader_type == VERTEX_SHADER {
-            "vertex"
+        let stage = if shader_type == VERTEX_SHADER {
+            ShaderStage::Vertex
         } else {
-            "fragment"
+            ShaderStage::Fragment
         };
-        Err(context
-            .get_shader_info_log(&shader)
-            .map(|s| {
-                format!(
-                    "Could not compile {} shaders because of:\n{}",
-                    shader_name, s
-                )
-            })
-            .unwrap_or_else(|| String::from("Unknown error creating shader")))
+        Err(Error::ShaderCompile {
+            stage,
+            log: context
+                .get_shader_info_log(&shader)
+                .unwrap_or_else(|| String::from("Unknown error creating shader")),
+        })
     }
 }
 
-impl Drop for GlProgram {
+impl Drop for SharedProgram {
     fn drop(&mut self) {
-        self.gl_context.delete_program(Some(&self.program));
+        self.gl_context.delete_program(Some(&self.program.borrow()));
     }
 }
@@ -0,0 +1,111 @@
+use super::{vao::GlVAO, *};
+
+/// Offscreen target the scene is drawn into when rendering with MSAA
+/// or at a resolution different from the canvas
+pub(super) struct RenderTarget {
+    context: GlContext,
+    scale: f32,
+    draw: GlFramebuffer,
+    /// Multisampled framebuffers can't be sampled, they are resolved into this
+    /// one of the same size first
+    resolve: Option<GlFramebuffer>,
+}
+
+impl RenderTarget {
+    pub(super) fn new(
+        context: &GlContext,
+        samples: i32,
+        scale: f32,
+        canvas_size: (i32, i32),
+    ) -> Result<Self, Error> {
+        let size = Self::scaled_size(scale, canvas_size);
+        let draw = GlFramebuffer::new(
+            context,
+            FramebufferOptions {
+                color: if samples > 0 {
+                    Attachment::Renderbuffer(TextureFormat::Rgba8)
+                } else {
+                    Attachment::Texture(TextureFormat::Rgba8)
+                },
+                depth: Attachment::Renderbuffer(TextureFormat::Depth24),
+                samples,
+            },
+            size.0,
+            size.1,
+        )?;
+
+        let resolve = if samples > 0 {
+            Some(GlFramebuffer::new(
+                context,
+                FramebufferOptions {
+                    color: Attachment::Texture(TextureFormat::Rgba8),
+                    depth: Attachment::None,
+                    samples: 0,
+                },
+                size.0,
+                size.1,
+            )?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            context: context.clone(),
+            scale,
+            draw,
+            resolve,
+        })
+    }
+
+    pub(super) fn scaled_size(scale: f32, canvas_size: (i32, i32)) -> (i32, i32) {
+        (
+            ((canvas_size.0 as f32 * scale).round() as i32).max(1),
+            ((canvas_size.1 as f32 * scale).round() as i32).max(1),
+        )
+    }
+
+    pub(super) fn size(&self) -> (i32, i32) {
+        (self.draw.width(), self.draw.height())
+    }
+
+    pub(super) fn bind(&self) {
+        self.draw.bind();
+    }
+
+    /// Whether the target still has the scaled size of a canvas of `canvas_size`
+    pub(super) fn fits(&self, canvas_size: (i32, i32)) -> bool {
+        Self::scaled_size(self.scale, canvas_size) == self.size()
+    }
+
+    /// Stretches the frame over the canvas with `copy`, a `COPY_FRAGMENT_SOURCE` program.
+    /// The frame is drawn rather than blitted as the canvas is multisampled when the
+    /// context is antialiased, and blits into multisampled framebuffers are invalid.
+    pub(super) fn present(
+        &self,
+        canvas_size: (i32, i32),
+        copy: &GlProgram,
+        vao: &GlVAO,
+    ) -> Result<(), Error> {
+        let source = match &self.resolve {
+            Some(resolve) => {
+                self.draw.blit(Some(resolve), self.size())?;
+                resolve
+            }
+            None => &self.draw,
+        };
+        let texture = source
+            .color_texture()
+            .ok_or_else(|| Error::Invalid(String::from("Render target without color texture")))?;
+
+        self.context
+            .bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        self.context.viewport(0, 0, canvas_size.0, canvas_size.1);
+        self.context.disable(WebGl2RenderingContext::DEPTH_TEST);
+        vao.bind();
+        copy.bind_texture("source", &texture, 0);
+        self.context
+            .draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, 3);
+        self.context.enable(WebGl2RenderingContext::DEPTH_TEST);
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+use super::*;
+
+/// Slot of the node and generation of the slot, so the id of a removed node
+/// doesn't alias a node added later in the same slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MeshId(usize);
+
+/// Local transform of a node, applied as scale, then rotation, then translation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: [f32; 3],
+    pub rotation_axis: [f32; 3],
+    /// Radians around `rotation_axis`
+    pub rotation_angle: f32,
+    pub scale: [f32; 3],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: [0., 0., 0.],
+            rotation_axis: [0., 0., 1.],
+            rotation_angle: 0.,
+            scale: [1., 1., 1.],
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4 {
+        let rotation_scale = mat4_mul(
+            &mat4_rotation(self.rotation_axis, self.rotation_angle),
+            &mat4_scaling(self.scale),
+        );
+        mat4_mul(&mat4_translation(self.translation), &rotation_scale)
+    }
+}
+
+pub struct Node {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    transform: Transform,
+    world: Mat4,
+    /// The world transform of this node and its descendants is outdated
+    dirty: bool,
+    visible: bool,
+    mesh: Option<MeshId>,
+    color: [f32; 4],
+}
+
+impl Node {
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) -> &mut Self {
+        self.transform = transform;
+        self.dirty = true;
+        self
+    }
+
+    /// Last computed world transform, see `Scene::update`
+    pub fn world_transform(&self) -> &Mat4 {
+        &self.world
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Hidden nodes aren't drawn, along with their descendants
+    pub fn set_visible(&mut self, visible: bool) -> &mut Self {
+        self.visible = visible;
+        self
+    }
+
+    pub fn mesh(&self) -> Option<MeshId> {
+        self.mesh
+    }
+
+    pub fn set_mesh(&mut self, mesh: Option<MeshId>) -> &mut Self {
+        self.mesh = mesh;
+        self
+    }
+
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
+
+    pub fn set_color(&mut self, color: &[f32; 4]) -> &mut Self {
+        self.color = *color;
+        self
+    }
+}
+
+/// Hierarchy of nodes drawn as instances of 3D meshes.
+///
+/// ```ignore
+/// let cube = scene.add_mesh(gl.create_mesh_3d(&CUBE)?);
+/// let arm = scene.add_node(None)?;
+/// let hand = scene.add_node(Some(arm))?;
+/// scene.node_mut(hand)?.set_mesh(Some(cube)).set_transform(transform);
+/// scene.draw();
+/// ```
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Option<Node>>,
+    /// Incremented when the node of the slot is removed
+    generations: Vec<u32>,
+    free_nodes: Vec<usize>,
+    roots: Vec<NodeId>,
+    meshes: Vec<GlMesh<InstanceProperties3D>>,
+}
+
+impl Scene {
+    /// Scenes draw meshes created with `create_mesh_3d`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_mesh(&mut self, mesh: GlMesh<InstanceProperties3D>) -> MeshId {
+        self.meshes.push(mesh);
+        MeshId(self.meshes.len() - 1)
+    }
+
+    pub fn mesh_mut(&mut self, mesh: MeshId) -> Option<&mut GlMesh<InstanceProperties3D>> {
+        self.meshes.get_mut(mesh.0)
+    }
+
+    /// Adds an empty visible node, at the root of the scene when `parent` is `None`
+    pub fn add_node(&mut self, parent: Option<NodeId>) -> Result<NodeId, Error> {
+        if let Some(parent) = parent {
+            self.node(parent)?;
+        }
+
+        let node = Node {
+            parent,
+            children: vec![],
+            transform: Transform::default(),
+            world: IDENTITY_MAT4,
+            dirty: true,
+            visible: true,
+            mesh: None,
+            color: [1., 1., 1., 1.],
+        };
+        let index = match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(node);
+                index
+            }
+            None => {
+                self.nodes.push(Some(node));
+                self.generations.push(0);
+                self.nodes.len() - 1
+            }
+        };
+        let id = NodeId {
+            index,
+            generation: self.generations[index],
+        };
+        self.siblings(parent).push(id);
+        Ok(id)
+    }
+
+    /// Removes the node and its descendants
+    pub fn remove_node(&mut self, id: NodeId) -> Result<(), Error> {
+        let parent = self.node(id)?.parent;
+        self.siblings(parent).retain(|&child| child != id);
+
+        let mut removed = vec![id];
+        while let Some(id) = removed.pop() {
+            if let Some(node) = self.nodes[id.index].take() {
+                removed.extend(node.children);
+                self.generations[id.index] = self.generations[id.index].wrapping_add(1);
+                self.free_nodes.push(id.index);
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the node under `parent`, keeping its local transform
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), Error> {
+        let mut ancestor = parent;
+        while let Some(ancestor_id) = ancestor {
+            if ancestor_id == id {
+                return Err(Error::Invalid(format!(
+                    "{:?} can't be a descendant of itself",
+                    id
+                )));
+            }
+            ancestor = self.node(ancestor_id)?.parent;
+        }
+
+        let old_parent = self.node(id)?.parent;
+        self.siblings(old_parent).retain(|&child| child != id);
+        self.siblings(parent).push(id);
+
+        let node = self.node_mut(id)?;
+        node.parent = parent;
+        node.dirty = true;
+        Ok(())
+    }
+
+    pub fn node(&self, id: NodeId) -> Result<&Node, Error> {
+        self.check(id)?;
+        self.nodes[id.index]
+            .as_ref()
+            .ok_or_else(|| Error::Invalid(format!("{:?} is not in the scene", id)))
+    }
+
+    pub fn node_mut(&mut self, id: NodeId) -> Result<&mut Node, Error> {
+        self.check(id)?;
+        self.nodes[id.index]
+            .as_mut()
+            .ok_or_else(|| Error::Invalid(format!("{:?} is not in the scene", id)))
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Recomputes the world transforms of the nodes changed since the last update
+    /// and of their descendants
+    pub fn update(&mut self) {
+        let mut stack: Vec<(NodeId, Mat4, bool)> = self
+            .roots
+            .iter()
+            .rev()
+            .map(|&root| (root, IDENTITY_MAT4, false))
+            .collect();
+
+        while let Some((id, parent_world, parent_dirty)) = stack.pop() {
+            let node = match self.nodes[id.index].as_mut() {
+                Some(node) => node,
+                None => continue,
+            };
+            let dirty = node.dirty || parent_dirty;
+            if dirty {
+                node.world = mat4_mul(&parent_world, &node.transform.matrix());
+                node.dirty = false;
+            }
+            for &child in node.children.iter().rev() {
+                stack.push((child, node.world, dirty));
+            }
+        }
+    }
+
+    /// Updates the scene and draws the visible nodes, one instanced draw per mesh
+    pub fn draw(&mut self) {
+        self.update();
+
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        while let Some(id) = stack.pop() {
+            let node = match &self.nodes[id.index] {
+                Some(node) if node.visible => node,
+                _ => continue,
+            };
+            if let Some(mesh) = node.mesh.and_then(|mesh| self.meshes.get_mut(mesh.0)) {
+                mesh.create_instance(
+                    InstanceProperties3D::new()
+                        .color(&node.color)
+                        .transform(&node.world),
+                );
+            }
+            stack.extend(node.children.iter().rev());
+        }
+
+        for mesh in &mut self.meshes {
+            mesh.draw();
+        }
+    }
+
+    /// Instances drawn and culled by the last `draw` over all meshes
+    pub fn cull_stats(&self) -> CullStats {
+        let mut stats = CullStats::default();
+        for mesh in &self.meshes {
+            stats += mesh.cull_stats();
+        }
+        stats
+    }
+
+    /// Fails if the slot was reused since `id` was added
+    fn check(&self, id: NodeId) -> Result<(), Error> {
+        match self.generations.get(id.index) {
+            Some(&generation) if generation == id.generation => Ok(()),
+            _ => Err(Error::Invalid(format!("{:?} is not in the scene", id))),
+        }
+    }
+
+    fn siblings(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent.and_then(|parent| self.nodes[parent.index].as_mut()) {
+            Some(parent) => &mut parent.children,
+            None => &mut self.roots,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_ids_dont_alias_reused_slots() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(None).unwrap();
+        let child = scene.add_node(Some(parent)).unwrap();
+        scene.remove_node(parent).unwrap();
+
+        let reused = scene.add_node(None).unwrap();
+        let other = scene.add_node(None).unwrap();
+        assert!(scene.node(child).is_err());
+        assert!(scene.node(parent).is_err());
+        assert!(scene.node(reused).is_ok());
+        assert!(scene.remove_node(child).is_err());
+        assert!(scene.set_parent(other, Some(parent)).is_err());
+        assert_eq!(scene.roots(), &[reused, other]);
+    }
+
+    #[test]
+    fn world_transforms_follow_the_parents() {
+        let mut scene = Scene::new();
+        let parent = scene.add_node(None).unwrap();
+        let child = scene.add_node(Some(parent)).unwrap();
+        let translation = |x| Transform {
+            translation: [x, 0., 0.],
+            ..Transform::default()
+        };
+        scene
+            .node_mut(parent)
+            .unwrap()
+            .set_transform(translation(1.));
+        scene
+            .node_mut(child)
+            .unwrap()
+            .set_transform(translation(2.));
+        scene.update();
+        assert_eq!(scene.node(child).unwrap().world_transform()[12], 3.);
+
+        scene
+            .node_mut(parent)
+            .unwrap()
+            .set_transform(translation(5.));
+        scene.update();
+        assert_eq!(scene.node(child).unwrap().world_transform()[12], 7.);
+    }
+}
@@ -1,17 +1,44 @@
-pub const VERTEX_SOURCE: &str = r##"#version 300 es
+/// GLSL function rounding a clip space position to the device pixels of the viewport when
+/// `pixel_snap` is set, for shaders declaring the `Camera` uniform block
+macro_rules! snap_to_pixel {
+    () => {
+        r##"
+vec4 snap_to_pixel(vec4 clip_position) {
+	if (pixel_snap > 0.) {
+		highp vec2 pixel = (clip_position.xy / clip_position.w * 0.5 + 0.5) * viewport_size;
+		clip_position.xy = (floor(pixel + 0.5) / viewport_size * 2. - 1.) * clip_position.w;
+	}
+	return clip_position;
+}
+"##
+    };
+}
+
+pub const VERTEX_SOURCE: &str = concat!(
+    r##"#version 300 es
 precision mediump float;
 layout(location=0) in vec2 vertex_coord;
 layout(location=1) in vec3 position;
 layout(location=2) in vec4 color;
 layout(location=3) in mat2 matrix;
 
-out vec4 fr_color;
+layout(std140) uniform Camera {
+	mat4 view_projection;
+	// 1 to round vertices to the device pixels of the viewport, see `PixelCamera`
+	float pixel_snap;
+	highp vec2 viewport_size;
+};
 
+out vec4 fr_color;
+"##,
+    snap_to_pixel!(),
+    r##"
 void main() {
-	gl_Position = vec4(vec3(vertex_coord * matrix, 0.) + position, 1.);
+	gl_Position = snap_to_pixel(view_projection * vec4(vec3(vertex_coord * matrix, 0.) + position, 1.));
 	fr_color = color;
 }
-"##;
+"##,
+);
 
 pub const FRAGMENT_SOURCE: &str = r##"#version 300 es
 precision mediump float;
@@ -23,3 +50,368 @@ void main() {
 	outColor = fr_color;
 }
 "##;
+
+pub const VERTEX_3D_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+layout(location=0) in vec3 vertex_coord;
+layout(location=1) in vec4 color;
+layout(location=2) in mat4 model;
+
+layout(std140) uniform Camera {
+	mat4 view_projection;
+	// 1 to round vertices to the device pixels of the viewport, see `PixelCamera`
+	float pixel_snap;
+	highp vec2 viewport_size;
+};
+
+out vec4 fr_color;
+out vec3 fr_world_position;
+
+void main() {
+	vec4 world_position = model * vec4(vertex_coord, 1.);
+	gl_Position = view_projection * world_position;
+	fr_color = color;
+	fr_world_position = world_position.xyz;
+}
+"##;
+
+/// Flat shading with normals derived from the screen space derivatives of the position
+pub const FRAGMENT_3D_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+in vec4 fr_color;
+in vec3 fr_world_position;
+out vec4 outColor;
+
+const vec3 light_direction = normalize(vec3(0.4, 1., 0.6));
+const float ambient = 0.3;
+
+void main() {
+	vec3 normal = normalize(cross(dFdx(fr_world_position), dFdy(fr_world_position)));
+	float diffuse = max(dot(normal, light_direction), 0.);
+	outColor = vec4(fr_color.rgb * (ambient + (1. - ambient) * diffuse), fr_color.a);
+}
+"##;
+
+/// Same transform as `VERTEX_SOURCE`, writing the instance index for `Picker`
+pub const PICK_VERTEX_SOURCE: &str = concat!(
+    r##"#version 300 es
+precision mediump float;
+layout(location=0) in vec2 vertex_coord;
+layout(location=1) in vec3 position;
+layout(location=3) in mat2 matrix;
+
+layout(std140) uniform Camera {
+	mat4 view_projection;
+	float pixel_snap;
+	highp vec2 viewport_size;
+};
+
+flat out uint fr_instance;
+"##,
+    snap_to_pixel!(),
+    r##"
+void main() {
+	gl_Position = snap_to_pixel(view_projection * vec4(vec3(vertex_coord * matrix, 0.) + position, 1.));
+	fr_instance = uint(gl_InstanceID);
+}
+"##,
+);
+
+/// Same transform as `VERTEX_3D_SOURCE`, writing the instance index for `Picker`
+pub const PICK_VERTEX_3D_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+layout(location=0) in vec3 vertex_coord;
+layout(location=2) in mat4 model;
+
+layout(std140) uniform Camera {
+	mat4 view_projection;
+	float pixel_snap;
+	highp vec2 viewport_size;
+};
+
+flat out uint fr_instance;
+
+void main() {
+	gl_Position = view_projection * model * vec4(vertex_coord, 1.);
+	fr_instance = uint(gl_InstanceID);
+}
+"##;
+
+/// Instance indices are offset by one so that 0 means nothing was drawn
+pub const PICK_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision highp float;
+precision highp int;
+
+uniform uint mesh_id;
+
+flat in uint fr_instance;
+out uvec2 id;
+
+void main() {
+	id = uvec2(mesh_id, fr_instance + 1u);
+}
+"##;
+
+pub const SPRITE_VERTEX_SOURCE: &str = concat!(
+    r##"#version 300 es
+precision mediump float;
+layout(location=0) in vec2 vertex_coord;
+layout(location=1) in vec3 position;
+layout(location=2) in vec4 color;
+layout(location=3) in mat2 matrix;
+layout(location=5) in vec4 uv_rect;
+layout(location=6) in float layer;
+
+layout(std140) uniform Camera {
+	mat4 view_projection;
+	// 1 to round vertices to the device pixels of the viewport, see `PixelCamera`
+	float pixel_snap;
+	highp vec2 viewport_size;
+};
+
+out vec4 fr_color;
+out vec3 fr_uv;
+"##,
+    snap_to_pixel!(),
+    r##"
+void main() {
+	gl_Position = snap_to_pixel(view_projection * vec4(vec3(vertex_coord * matrix, 0.) + position, 1.));
+	fr_color = color;
+
+	vec2 t = vec2(vertex_coord.x + 0.5, 0.5 - vertex_coord.y);
+	fr_uv = vec3(mix(uv_rect.xy, uv_rect.zw, t), layer);
+}
+"##,
+);
+
+pub const SPRITE_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+precision mediump sampler2DArray;
+
+uniform sampler2DArray atlas;
+
+in vec4 fr_color;
+in vec3 fr_uv;
+out vec4 outColor;
+
+void main() {
+	outColor = texture(atlas, fr_uv) * fr_color;
+	if (outColor.a < 0.01) discard;
+}
+"##;
+
+pub const TEXT_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+precision mediump sampler2DArray;
+
+uniform sampler2DArray atlas;
+
+in vec4 fr_color;
+in vec3 fr_uv;
+out vec4 outColor;
+
+void main() {
+	float distance = texture(atlas, fr_uv).a;
+	float smoothing = fwidth(distance);
+	float alpha = smoothstep(0.5 - smoothing, 0.5 + smoothing, distance);
+
+	outColor = vec4(fr_color.rgb, fr_color.a * alpha);
+	if (outColor.a < 0.01) discard;
+}
+"##;
+
+/// Fullscreen triangle without vertex buffers, draw it with 3 vertices
+pub const FULLSCREEN_VERTEX_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+out vec2 uv;
+
+void main() {
+	vec2 p = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+	uv = p;
+	gl_Position = vec4(p * 2. - 1., 0., 1.);
+}
+"##;
+
+pub const COPY_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+
+in vec2 uv;
+out vec4 outColor;
+
+void main() {
+	outColor = texture(source, uv);
+}
+"##;
+
+pub const THRESHOLD_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+uniform float threshold;
+
+in vec2 uv;
+out vec4 outColor;
+
+void main() {
+	vec4 color = texture(source, uv);
+	float brightness = max(color.r, max(color.g, color.b));
+	outColor = vec4(color.rgb * max(brightness - threshold, 0.) / max(brightness, 0.0001), 1.);
+}
+"##;
+
+/// Separable 9 tap gaussian, `direction` is the texel offset between taps
+pub const BLUR_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+uniform vec2 direction;
+
+in vec2 uv;
+out vec4 outColor;
+
+void main() {
+	vec4 color = texture(source, uv) * 0.2270270270;
+	color += texture(source, uv + direction * 1.3846153846) * 0.3162162162;
+	color += texture(source, uv - direction * 1.3846153846) * 0.3162162162;
+	color += texture(source, uv + direction * 3.2307692308) * 0.0702702703;
+	color += texture(source, uv - direction * 3.2307692308) * 0.0702702703;
+	outColor = color;
+}
+"##;
+
+pub const BLOOM_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+uniform sampler2D bloom;
+uniform float intensity;
+
+in vec2 uv;
+out vec4 outColor;
+
+void main() {
+	vec4 color = texture(source, uv);
+	outColor = vec4(color.rgb + texture(bloom, uv).rgb * intensity, color.a);
+}
+"##;
+
+pub const VIGNETTE_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+uniform float intensity;
+uniform float smoothness;
+
+in vec2 uv;
+out vec4 outColor;
+
+void main() {
+	vec4 color = texture(source, uv);
+	float vignette = smoothstep(0.75, 0.75 - smoothness, length(uv - 0.5));
+	outColor = vec4(color.rgb * mix(1., vignette, intensity), color.a);
+}
+"##;
+
+/// `lut` is a strip of `lut_size` slices of `lut_size`² texels, blue grows with the slice
+pub const COLOR_GRADING_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+uniform sampler2D lut;
+uniform float lut_size;
+uniform float intensity;
+
+in vec2 uv;
+out vec4 outColor;
+
+vec3 lookup(vec3 color, float slice) {
+	vec2 texel = vec2(1. / (lut_size * lut_size), 1. / lut_size);
+	vec2 coord = vec2(
+		(slice * lut_size + color.r * (lut_size - 1.) + 0.5) * texel.x,
+		(color.g * (lut_size - 1.) + 0.5) * texel.y
+	);
+	return texture(lut, coord).rgb;
+}
+
+void main() {
+	vec4 color = texture(source, uv);
+	vec3 graded_color = clamp(color.rgb, 0., 1.);
+	float blue = graded_color.b * (lut_size - 1.);
+	vec3 graded = mix(
+		lookup(graded_color, floor(blue)),
+		lookup(graded_color, min(floor(blue) + 1., lut_size - 1.)),
+		fract(blue)
+	);
+	outColor = vec4(mix(color.rgb, graded, intensity), color.a);
+}
+"##;
+
+pub const FXAA_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+uniform vec2 texel;
+
+in vec2 uv;
+out vec4 outColor;
+
+const float REDUCE_MIN = 1. / 128.;
+const float REDUCE_MUL = 1. / 8.;
+const float SPAN_MAX = 8.;
+
+void main() {
+	vec3 luma_weights = vec3(0.299, 0.587, 0.114);
+	float luma_nw = dot(texture(source, uv + vec2(-1., -1.) * texel).rgb, luma_weights);
+	float luma_ne = dot(texture(source, uv + vec2(1., -1.) * texel).rgb, luma_weights);
+	float luma_sw = dot(texture(source, uv + vec2(-1., 1.) * texel).rgb, luma_weights);
+	float luma_se = dot(texture(source, uv + vec2(1., 1.) * texel).rgb, luma_weights);
+	vec4 center = texture(source, uv);
+	float luma_m = dot(center.rgb, luma_weights);
+
+	float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+	float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+	vec2 direction = vec2(
+		-((luma_nw + luma_ne) - (luma_sw + luma_se)),
+		(luma_nw + luma_sw) - (luma_ne + luma_se)
+	);
+	float reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+	float scale = 1. / (min(abs(direction.x), abs(direction.y)) + reduce);
+	direction = clamp(direction * scale, -SPAN_MAX, SPAN_MAX) * texel;
+
+	vec3 a = 0.5 * (
+		texture(source, uv + direction * (1. / 3. - 0.5)).rgb +
+		texture(source, uv + direction * (2. / 3. - 0.5)).rgb
+	);
+	vec3 b = a * 0.5 + 0.25 * (
+		texture(source, uv - direction * 0.5).rgb +
+		texture(source, uv + direction * 0.5).rgb
+	);
+	float luma_b = dot(b, luma_weights);
+
+	outColor = vec4(luma_b < luma_min || luma_b > luma_max ? a : b, center.a);
+}
+"##;
+
+/// ACES filmic approximation followed by gamma correction
+pub const TONE_MAPPING_FRAGMENT_SOURCE: &str = r##"#version 300 es
+precision mediump float;
+
+uniform sampler2D source;
+uniform float exposure;
+uniform float gamma;
+
+in vec2 uv;
+out vec4 outColor;
+
+void main() {
+	vec4 color = texture(source, uv);
+	vec3 x = color.rgb * exposure;
+	vec3 mapped = clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0., 1.);
+	outColor = vec4(pow(mapped, vec3(1. / gamma)), color.a);
+}
+"##;
@@ -0,0 +1,152 @@
+use super::{buffer::GlBuffer, vao::Attrib, vao::GlVAO, *};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sprite(usize);
+
+/// Draws any number of sprites from the same atlas with a single instanced draw call
+pub struct SpriteBatch {
+    context: GlContext,
+    program: GlProgram,
+    atlas: GlAtlas,
+    instances_data: Vec<f32>,
+    instances_buffer: GlBuffer,
+    vertices_buffer: GlBuffer,
+    vao: GlVAO,
+    instances_count: i32,
+    counters: Rc<Cell<RenderCounters>>,
+}
+
+const QUAD_VERTICES: [f32; 12] = [
+    -0.5, -0.5, -0.5, 0.5, 0.5, -0.5, 0.5, 0.5, 0.5, -0.5, -0.5, 0.5,
+];
+
+impl SpriteBatch {
+    pub(super) fn new(
+        context: &GlContext,
+        counters: &Rc<Cell<RenderCounters>>,
+        page_size: i32,
+    ) -> Result<Self, Error> {
+        Self::with_fragment_shader(
+            context,
+            counters,
+            page_size,
+            shaders::SPRITE_FRAGMENT_SOURCE,
+        )
+    }
+
+    /// `fragment_shader_src` receives `fr_color`, `fr_uv` and the `atlas` sampler
+    pub(super) fn with_fragment_shader(
+        context: &GlContext,
+        counters: &Rc<Cell<RenderCounters>>,
+        page_size: i32,
+        fragment_shader_src: &str,
+    ) -> Result<Self, Error> {
+        let program = GlProgram::new(context, shaders::SPRITE_VERTEX_SOURCE, fragment_shader_src)?;
+
+        let vertices_buffer = GlBuffer::new_static(context, &QUAD_VERTICES)?;
+        let instances_buffer = GlBuffer::new(context)?;
+
+        let batch = Self {
+            context: context.clone(),
+            program,
+            atlas: GlAtlas::new(context, page_size)?,
+            instances_data: vec![],
+            instances_buffer,
+            vertices_buffer,
+            vao: GlVAO::new(context)?,
+            instances_count: 0,
+            counters: counters.clone(),
+        };
+        batch.link();
+        Ok(batch)
+    }
+
+    fn link(&self) {
+        self.vao
+            .link_buffer(&self.vertices_buffer, &[Attrib::VecF32(0, 2)]);
+        self.vao.link_instance_buffer(
+            &self.instances_buffer,
+            &[
+                Attrib::VecF32(1, 3),
+                Attrib::VecF32(2, 4),
+                Attrib::MatF32(3, 2, 2),
+                Attrib::VecF32(5, 4),
+                Attrib::VecF32(6, 1),
+            ],
+        );
+    }
+
+    /// Adds a `width * height` RGBA image to the atlas
+    pub fn add_sprite(&mut self, width: i32, height: i32, rgba: Vec<u8>) -> Result<Sprite, Error> {
+        Ok(Sprite(self.atlas.insert(width, height, rgba)?))
+    }
+
+    /// The image must be already loaded (`HtmlImageElement::complete`)
+    pub fn add_sprite_from_image(&mut self, image: &HtmlImageElement) -> Result<Sprite, Error> {
+        Ok(Sprite(self.atlas.insert_html_image(image)?))
+    }
+
+    pub fn atlas(&self) -> &GlAtlas {
+        &self.atlas
+    }
+
+    /// `instance.color` tints the sprite
+    pub fn create_instance(&mut self, sprite: Sprite, instance: &InstanceProperties) {
+        let region = self.atlas.region(sprite.0);
+        self.instances_data.extend_from_slice(&instance.0);
+        self.instances_data.extend_from_slice(&region.uv);
+        self.instances_data.push(region.layer as f32);
+        self.instances_count += 1;
+    }
+
+    pub fn draw(&mut self) {
+        self.instances_buffer.update(&self.instances_data);
+        RenderCounters::add(&self.counters, 1, self.instances_count, &self.instances_data);
+
+        self.program.bind_texture("atlas", self.atlas.texture(), 0);
+        self.vao.bind();
+
+        // The blending state of the caller is restored after the draw
+        let blend = self.context.is_enabled(WebGl2RenderingContext::BLEND);
+        let blend_func = [
+            WebGl2RenderingContext::BLEND_SRC_RGB,
+            WebGl2RenderingContext::BLEND_DST_RGB,
+            WebGl2RenderingContext::BLEND_SRC_ALPHA,
+            WebGl2RenderingContext::BLEND_DST_ALPHA,
+        ]
+        .map(|name| {
+            self.context
+                .get_parameter(name)
+                .ok()
+                .and_then(|value| value.as_f64())
+                .map_or(WebGl2RenderingContext::ONE, |value| value as u32)
+        });
+
+        self.context.enable(WebGl2RenderingContext::BLEND);
+        self.context.blend_func(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+        );
+
+        const PRIMITIVE: u32 = WebGl2RenderingContext::TRIANGLES;
+        self.context.draw_arrays_instanced(
+            PRIMITIVE,
+            0,
+            QUAD_VERTICES.len() as i32 / 2,
+            self.instances_count,
+        );
+
+        if !blend {
+            self.context.disable(WebGl2RenderingContext::BLEND);
+        }
+        self.context.blend_func_separate(
+            blend_func[0],
+            blend_func[1],
+            blend_func[2],
+            blend_func[3],
+        );
+
+        self.instances_count = 0;
+        self.instances_data.truncate(0);
+    }
+}
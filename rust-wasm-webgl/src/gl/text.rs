@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+use super::*;
+use crate::text::*;
+
+/// Pixels per em of the generated distance fields
+const SDF_SIZE: f32 = 48.;
+/// Distance in pixels covered by the distance fields on each side of the outline
+const SDF_SPREAD: f32 = 6.;
+
+#[derive(Debug, Clone, Copy)]
+pub struct TextStyle {
+    /// Top left corner of the text block
+    pub position: [f32; 3],
+    pub color: [f32; 4],
+    pub layout: LayoutOptions,
+}
+
+#[derive(Clone, Copy)]
+struct GlyphSprite {
+    sprite: Sprite,
+    left: f32,
+    top: f32,
+    width: f32,
+    height: f32,
+}
+
+/// Draws text with signed distance field glyphs, which stay sharp at any scale.
+/// Glyphs are rasterized on first use.
+pub struct TextRenderer {
+    font: Font,
+    batch: SpriteBatch,
+    glyphs: HashMap<u16, Option<GlyphSprite>>,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            position: [0., 0., 0.],
+            color: [1., 1., 1., 1.],
+            layout: LayoutOptions::default(),
+        }
+    }
+}
+
+impl TextRenderer {
+    pub(super) fn new(
+        context: &GlContext,
+        counters: &Rc<Cell<RenderCounters>>,
+        font: Font,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            font,
+            batch: SpriteBatch::with_fragment_shader(
+                context,
+                counters,
+                1024,
+                shaders::TEXT_FRAGMENT_SOURCE,
+            )?,
+            glyphs: HashMap::new(),
+        })
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    pub fn measure(&self, text: &str, style: &TextStyle) -> TextLayout {
+        layout_text(&self.font, text, &style.layout)
+    }
+
+    pub fn create_text(&mut self, text: &str, style: &TextStyle) -> Result<(), Error> {
+        let layout = layout_text(&self.font, text, &style.layout);
+        let pixel_size = style.layout.size / SDF_SIZE;
+
+        for positioned in &layout.glyphs {
+            if let Some(glyph) = self.glyph(positioned.glyph)? {
+                let width = glyph.width * pixel_size;
+                let height = glyph.height * pixel_size;
+                self.batch.create_instance(
+                    glyph.sprite,
+                    InstanceProperties::new()
+                        .position(&[
+                            style.position[0] + positioned.x + glyph.left * pixel_size + width / 2.,
+                            style.position[1] + positioned.y + glyph.top * pixel_size - height / 2.,
+                            style.position[2],
+                        ])
+                        .color(&style.color)
+                        .matrix(&[width, 0., 0., height]),
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub fn draw(&mut self) {
+        self.batch.draw();
+    }
+
+    fn glyph(&mut self, glyph: u16) -> Result<Option<GlyphSprite>, Error> {
+        if !self.glyphs.contains_key(&glyph) {
+            let outline = self.font.outline(glyph)?;
+            let sprite = if outline.segments.is_empty() {
+                None
+            } else {
+                let sdf = generate_sdf(&outline, SDF_SIZE / self.font.units_per_em(), SDF_SPREAD);
+                let rgba = sdf
+                    .pixels
+                    .iter()
+                    .flat_map(|&d| [255, 255, 255, d])
+                    .collect();
+                Some(GlyphSprite {
+                    sprite: self.batch.add_sprite(sdf.width, sdf.height, rgba)?,
+                    left: sdf.left,
+                    top: sdf.top,
+                    width: sdf.width as f32,
+                    height: sdf.height as f32,
+                })
+            };
+            self.glyphs.insert(glyph, sprite);
+        }
+        Ok(self.glyphs[&glyph])
+    }
+}
@@ -0,0 +1,752 @@
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
+
+use web_sys::*;
+
+use super::context::GlContext;
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8,
+    Srgb8Alpha8,
+    R8,
+    R32F,
+    Rgba16F,
+    Rgba32F,
+    /// Two unsigned integers, can't be sampled with filtering
+    Rg32UI,
+    Depth24,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+}
+
+/// Uploaded data is kept on the CPU to restore the texture after a context loss
+pub struct GlTexture {
+    shared: Rc<SharedTexture>,
+}
+
+struct SharedTexture {
+    context: GlContext,
+    texture: RefCell<WebGlTexture>,
+    target: u32,
+    format: TextureFormat,
+    width: i32,
+    height: i32,
+    layers: i32,
+    min_filter: Cell<Filter>,
+    mag_filter: Cell<Filter>,
+    wrap: Cell<(Wrap, Wrap)>,
+    mipmaps: Cell<bool>,
+    source: RefCell<TextureSource>,
+    /// Images uploaded so far, uploaded again after `source`
+    images: RefCell<Vec<ImageUpload>>,
+}
+
+/// Copy of every layer, filled by the uploads
+enum TextureSource {
+    None,
+    Bytes(Vec<u8>),
+    Floats(Vec<f32>),
+}
+
+struct ImageUpload {
+    x: i32,
+    y: i32,
+    layer: i32,
+    image: HtmlImageElement,
+}
+
+const TEXTURE_2D: u32 = WebGl2RenderingContext::TEXTURE_2D;
+const TEXTURE_2D_ARRAY: u32 = WebGl2RenderingContext::TEXTURE_2D_ARRAY;
+
+impl GlTexture {
+    pub(super) fn new_2d(
+        context: &GlContext,
+        format: TextureFormat,
+        width: i32,
+        height: i32,
+    ) -> Result<GlTexture, Error> {
+        let texture = Self::create(context, TEXTURE_2D, format, width, height, 1)?;
+        texture.allocate()?;
+        Ok(texture)
+    }
+
+    pub(super) fn new_2d_array(
+        context: &GlContext,
+        format: TextureFormat,
+        width: i32,
+        height: i32,
+        layers: i32,
+    ) -> Result<GlTexture, Error> {
+        let texture = Self::create(context, TEXTURE_2D_ARRAY, format, width, height, layers)?;
+        texture.allocate()?;
+        Ok(texture)
+    }
+
+    pub(super) fn from_image(
+        context: &GlContext,
+        format: TextureFormat,
+        image: &HtmlImageElement,
+    ) -> Result<GlTexture, Error> {
+        let mut texture = Self::new_2d(
+            context,
+            format,
+            image.natural_width() as i32,
+            image.natural_height() as i32,
+        )?;
+        texture.upload_image(image)?;
+        Ok(texture)
+    }
+
+    fn create(
+        context: &GlContext,
+        target: u32,
+        format: TextureFormat,
+        width: i32,
+        height: i32,
+        layers: i32,
+    ) -> Result<GlTexture, Error> {
+        if width <= 0 || height <= 0 || layers <= 0 {
+            return Err(Error::Invalid(format!(
+                "Invalid texture size {}x{}x{}",
+                width, height, layers
+            )));
+        }
+
+        let mut texture = GlTexture {
+            shared: Rc::new(SharedTexture {
+                texture: RefCell::new(create_texture(context)?),
+                context: context.clone(),
+                target,
+                format,
+                width,
+                height,
+                layers,
+                min_filter: Cell::new(Filter::Linear),
+                mag_filter: Cell::new(Filter::Linear),
+                wrap: Cell::new((Wrap::ClampToEdge, Wrap::ClampToEdge)),
+                mipmaps: Cell::new(false),
+                source: RefCell::new(TextureSource::None),
+                images: RefCell::new(vec![]),
+            }),
+        };
+        context.register(&texture.shared, |shared| GlTexture { shared }.restore());
+
+        // Non mipmapped textures are incomplete with the default filter
+        let filter = if format.is_filterable() {
+            Filter::Linear
+        } else {
+            Filter::Nearest
+        };
+        texture.set_filter(filter, filter);
+        texture.set_wrap(Wrap::ClampToEdge, Wrap::ClampToEdge);
+        Ok(texture)
+    }
+
+    fn allocate(&self) -> Result<(), Error> {
+        let shared = &self.shared;
+        self.bind();
+        if shared.target == TEXTURE_2D_ARRAY {
+            shared.context.tex_image_3d_with_opt_u8_array(
+                shared.target,
+                0,
+                shared.format.internal_format() as i32,
+                shared.width,
+                shared.height,
+                shared.layers,
+                0,
+                shared.format.format(),
+                shared.format.data_type(),
+                None,
+            )
+        } else {
+            shared
+                .context
+                .tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
+                    shared.target,
+                    0,
+                    shared.format.internal_format() as i32,
+                    shared.width,
+                    shared.height,
+                    0,
+                    shared.format.format(),
+                    shared.format.data_type(),
+                    None,
+                )
+        }
+        .map_err(Error::js("Couldn't allocate texture"))
+    }
+
+    /// Recreates the texture after the context was restored, keeping its size,
+    /// sampling parameters and uploaded content
+    fn restore(mut self) -> Result<(), Error> {
+        *self.shared.texture.borrow_mut() = create_texture(&self.shared.context)?;
+        self.allocate()?;
+
+        let mipmaps = self.shared.mipmaps.replace(false);
+        let (min, mag) = (self.shared.min_filter.get(), self.shared.mag_filter.get());
+        self.set_filter(min, mag);
+        let (s, t) = self.shared.wrap.get();
+        self.set_wrap(s, t);
+
+        let (width, height, layers) = (self.width(), self.height(), self.layers());
+        match &*self.shared.source.borrow() {
+            TextureSource::None => {}
+            TextureSource::Bytes(bytes) => {
+                self.write_bytes([0; 3], [width, height, layers], bytes)?
+            }
+            TextureSource::Floats(floats) => self.write_floats(0, layers, floats)?,
+        }
+        for upload in self.shared.images.borrow().iter() {
+            self.write_image(upload.x, upload.y, upload.layer, &upload.image)?;
+        }
+        if mipmaps {
+            self.generate_mipmaps()?;
+        }
+        Ok(())
+    }
+
+    pub fn bind(&self) {
+        let shared = &self.shared;
+        shared
+            .context
+            .bind_texture(shared.target, Some(&shared.texture.borrow()));
+    }
+
+    pub(super) fn gl_texture(&self) -> WebGlTexture {
+        self.shared.texture.borrow().clone()
+    }
+
+    /// Another handle to the same texture
+    pub(super) fn share(&self) -> GlTexture {
+        GlTexture {
+            shared: self.shared.clone(),
+        }
+    }
+
+    pub fn bind_to_unit(&self, unit: u32) {
+        self.shared
+            .context
+            .active_texture(WebGl2RenderingContext::TEXTURE0 + unit);
+        self.bind();
+    }
+
+    pub fn width(&self) -> i32 {
+        self.shared.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.shared.height
+    }
+
+    pub fn layers(&self) -> i32 {
+        self.shared.layers
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.shared.format
+    }
+
+    /// Replaces the whole image (or the whole `layer` of an array texture).
+    /// `data` must be tightly packed rows in the texture format.
+    pub fn upload(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.upload_layer(0, data)
+    }
+
+    pub fn upload_layer(&mut self, layer: i32, data: &[u8]) -> Result<(), Error> {
+        self.upload_region(0, 0, layer, self.width(), self.height(), data)
+    }
+
+    pub fn upload_region(
+        &mut self,
+        x: i32,
+        y: i32,
+        layer: i32,
+        width: i32,
+        height: i32,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        let format = self.format();
+        self.check_region(x, y, layer, width, height)?;
+        if format.is_float() {
+            return Err(Error::Invalid(format!(
+                "{:?} textures must be uploaded with `upload_f32`",
+                format
+            )));
+        }
+        if format.is_integer() {
+            return Err(Error::Invalid(format!(
+                "{:?} textures can't be uploaded",
+                format
+            )));
+        }
+
+        let expected_len = region_len(width, height, format.bytes_per_pixel())?;
+        if data.len() != expected_len {
+            return Err(Error::Invalid(format!(
+                "Expected {} bytes for a {}x{} {:?} region but found {}",
+                expected_len,
+                width,
+                height,
+                format,
+                data.len()
+            )));
+        }
+
+        self.write_bytes([x, y, layer], [width, height, 1], data)?;
+        self.keep_bytes(x, y, layer, width, height, data);
+        self.mipmaps_outdated();
+        Ok(())
+    }
+
+    /// Same as `upload_layer` for the float formats
+    pub fn upload_f32(&mut self, layer: i32, data: &[f32]) -> Result<(), Error> {
+        let (width, height, format) = (self.width(), self.height(), self.format());
+        self.check_region(0, 0, layer, width, height)?;
+
+        let expected_len = region_len(width, height, format.channels())?;
+        if !format.is_float() || data.len() != expected_len {
+            return Err(Error::Invalid(format!(
+                "Expected {} floats for a {}x{} {:?} texture but found {}",
+                expected_len,
+                width,
+                height,
+                format,
+                data.len()
+            )));
+        }
+
+        self.write_floats(layer, 1, data)?;
+        let mut source = self.shared.source.borrow_mut();
+        if !matches!(*source, TextureSource::Floats(_)) {
+            *source = TextureSource::Floats(vec![0.; expected_len * self.layers() as usize]);
+        }
+        if let TextureSource::Floats(floats) = &mut *source {
+            let start = layer as usize * expected_len;
+            floats[start..start + expected_len].copy_from_slice(data);
+        }
+        drop(source);
+
+        self.mipmaps_outdated();
+        Ok(())
+    }
+
+    pub fn upload_image(&mut self, image: &HtmlImageElement) -> Result<(), Error> {
+        self.upload_image_layer(0, image)
+    }
+
+    pub fn upload_image_layer(
+        &mut self,
+        layer: i32,
+        image: &HtmlImageElement,
+    ) -> Result<(), Error> {
+        self.upload_image_region(0, 0, layer, image)
+    }
+
+    pub fn upload_image_region(
+        &mut self,
+        x: i32,
+        y: i32,
+        layer: i32,
+        image: &HtmlImageElement,
+    ) -> Result<(), Error> {
+        let width = image.natural_width() as i32;
+        let height = image.natural_height() as i32;
+        self.check_region(x, y, layer, width, height)?;
+        self.write_image(x, y, layer, image)?;
+
+        // Images covering the whole layer replace the previous ones
+        let mut images = self.shared.images.borrow_mut();
+        if (x, y, width, height) == (0, 0, self.width(), self.height()) {
+            images.retain(|upload| upload.layer != layer);
+        }
+        images.push(ImageUpload {
+            x,
+            y,
+            layer,
+            image: image.clone(),
+        });
+        drop(images);
+
+        self.mipmaps_outdated();
+        Ok(())
+    }
+
+    pub fn set_filter(&mut self, min: Filter, mag: Filter) {
+        let shared = &self.shared;
+        shared.min_filter.set(min);
+        shared.mag_filter.set(mag);
+        self.bind();
+        let min = match (min, shared.mipmaps.get()) {
+            (Filter::Nearest, false) => WebGl2RenderingContext::NEAREST,
+            (Filter::Linear, false) => WebGl2RenderingContext::LINEAR,
+            (Filter::Nearest, true) => WebGl2RenderingContext::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, true) => WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR,
+        };
+        shared.context.tex_parameteri(
+            shared.target,
+            WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+            min as i32,
+        );
+        shared.context.tex_parameteri(
+            shared.target,
+            WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+            mag.gl_enum() as i32,
+        );
+    }
+
+    pub fn set_wrap(&mut self, s: Wrap, t: Wrap) {
+        let shared = &self.shared;
+        shared.wrap.set((s, t));
+        self.bind();
+        shared.context.tex_parameteri(
+            shared.target,
+            WebGl2RenderingContext::TEXTURE_WRAP_S,
+            s.gl_enum() as i32,
+        );
+        shared.context.tex_parameteri(
+            shared.target,
+            WebGl2RenderingContext::TEXTURE_WRAP_T,
+            t.gl_enum() as i32,
+        );
+    }
+
+    /// Generates the mipmap chain and switches minification to trilinear
+    /// sampling. Later uploads regenerate the chain automatically.
+    pub fn generate_mipmaps(&mut self) -> Result<(), Error> {
+        let shared = &self.shared;
+        if !shared.format.is_filterable() {
+            return Err(Error::Invalid(format!(
+                "{:?} textures can't have mipmaps",
+                shared.format
+            )));
+        }
+        self.bind();
+        shared.context.generate_mipmap(shared.target);
+
+        if !shared.mipmaps.replace(true) {
+            shared.context.tex_parameteri(
+                shared.target,
+                WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+                match shared.min_filter.get() {
+                    Filter::Nearest => WebGl2RenderingContext::NEAREST_MIPMAP_LINEAR,
+                    Filter::Linear => WebGl2RenderingContext::LINEAR_MIPMAP_LINEAR,
+                } as i32,
+            );
+        }
+        Ok(())
+    }
+
+    fn mipmaps_outdated(&self) {
+        if self.shared.mipmaps.get() {
+            self.shared.context.generate_mipmap(self.shared.target);
+        }
+    }
+
+    /// Uploads tightly packed rows to the `[width, height, layers]` region at `[x, y, layer]`
+    fn write_bytes(&self, offset: [i32; 3], size: [i32; 3], data: &[u8]) -> Result<(), Error> {
+        let ([x, y, layer], [width, height, depth]) = (offset, size);
+        let shared = &self.shared;
+        self.bind();
+        shared
+            .context
+            .pixel_storei(WebGl2RenderingContext::UNPACK_ALIGNMENT, 1);
+
+        if shared.target == TEXTURE_2D_ARRAY {
+            shared.context.tex_sub_image_3d_with_opt_u8_array(
+                shared.target,
+                0,
+                x,
+                y,
+                layer,
+                width,
+                height,
+                depth,
+                shared.format.format(),
+                shared.format.data_type(),
+                Some(data),
+            )
+        } else {
+            shared
+                .context
+                .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_u8_array(
+                    shared.target,
+                    0,
+                    x,
+                    y,
+                    width,
+                    height,
+                    shared.format.format(),
+                    shared.format.data_type(),
+                    Some(data),
+                )
+        }
+        .map_err(Error::js("Couldn't upload texture data"))
+    }
+
+    /// Copies the region into `source`
+    fn keep_bytes(&self, x: i32, y: i32, layer: i32, width: i32, height: i32, data: &[u8]) {
+        let bytes_per_pixel = self.format().bytes_per_pixel();
+        let texture_width = self.width() as usize;
+        let texture_height = self.height() as usize;
+
+        let mut source = self.shared.source.borrow_mut();
+        if !matches!(*source, TextureSource::Bytes(_)) {
+            let len = texture_width * texture_height * self.layers() as usize * bytes_per_pixel;
+            *source = TextureSource::Bytes(vec![0; len]);
+        }
+        if let TextureSource::Bytes(bytes) = &mut *source {
+            let row_len = width as usize * bytes_per_pixel;
+            for row in 0..height as usize {
+                let texture_row = layer as usize * texture_height + y as usize + row;
+                let start = (texture_row * texture_width + x as usize) * bytes_per_pixel;
+                bytes[start..start + row_len]
+                    .copy_from_slice(&data[row * row_len..(row + 1) * row_len]);
+            }
+        }
+    }
+
+    /// Uploads `depth` whole layers from `layer`
+    fn write_floats(&self, layer: i32, depth: i32, data: &[f32]) -> Result<(), Error> {
+        let shared = &self.shared;
+        self.bind();
+
+        // Same precautions as in `GlBuffer::update_slice`, no allocations
+        // while the view is alive.
+        let result = unsafe {
+            let array_buffer_view = js_sys::Float32Array::view(data);
+
+            if shared.target == TEXTURE_2D_ARRAY {
+                shared.context.tex_sub_image_3d_with_opt_array_buffer_view(
+                    shared.target,
+                    0,
+                    0,
+                    0,
+                    layer,
+                    shared.width,
+                    shared.height,
+                    depth,
+                    shared.format.format(),
+                    shared.format.data_type(),
+                    Some(&array_buffer_view),
+                )
+            } else {
+                shared
+                    .context
+                    .tex_sub_image_2d_with_i32_and_i32_and_u32_and_type_and_opt_array_buffer_view(
+                        shared.target,
+                        0,
+                        0,
+                        0,
+                        shared.width,
+                        shared.height,
+                        shared.format.format(),
+                        shared.format.data_type(),
+                        Some(&array_buffer_view),
+                    )
+            }
+        };
+        result.map_err(Error::js("Couldn't upload texture data"))
+    }
+
+    fn write_image(
+        &self,
+        x: i32,
+        y: i32,
+        layer: i32,
+        image: &HtmlImageElement,
+    ) -> Result<(), Error> {
+        let shared = &self.shared;
+        self.bind();
+        if shared.target == TEXTURE_2D_ARRAY {
+            shared.context.tex_sub_image_3d_with_html_image_element(
+                shared.target,
+                0,
+                x,
+                y,
+                layer,
+                image.natural_width() as i32,
+                image.natural_height() as i32,
+                1,
+                shared.format.format(),
+                shared.format.data_type(),
+                image,
+            )
+        } else {
+            shared
+                .context
+                .tex_sub_image_2d_with_u32_and_u32_and_html_image_element(
+                    shared.target,
+                    0,
+                    x,
+                    y,
+                    shared.format.format(),
+                    shared.format.data_type(),
+                    image,
+                )
+        }
+        .map_err(Error::js("Couldn't upload image to texture"))
+    }
+
+    fn check_region(
+        &self,
+        x: i32,
+        y: i32,
+        layer: i32,
+        width: i32,
+        height: i32,
+    ) -> Result<(), Error> {
+        let (texture_width, texture_height, layers) = (self.width(), self.height(), self.layers());
+        if x < 0
+            || y < 0
+            || layer < 0
+            || width <= 0
+            || height <= 0
+            || x.checked_add(width).is_none_or(|end| end > texture_width)
+            || y.checked_add(height).is_none_or(|end| end > texture_height)
+            || layer >= layers
+        {
+            Err(Error::Invalid(format!(
+                "Region {}x{} at ({}, {}, {}) is out of a {}x{}x{} texture",
+                width, height, x, y, layer, texture_width, texture_height, layers
+            )))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Values of a `width` x `height` region, checked as the sizes come from the caller
+fn region_len(width: i32, height: i32, per_pixel: usize) -> Result<usize, Error> {
+    usize::try_from(width)
+        .ok()
+        .zip(usize::try_from(height).ok())
+        .and_then(|(width, height)| width.checked_mul(height)?.checked_mul(per_pixel))
+        .ok_or_else(|| Error::Invalid(format!("Invalid region size {}x{}", width, height)))
+}
+
+fn create_texture(context: &WebGl2RenderingContext) -> Result<WebGlTexture, Error> {
+    context
+        .create_texture()
+        .ok_or_else(|| Error::Allocation(String::from("Unable to create gl texture")))
+}
+
+impl TextureFormat {
+    pub(super) fn internal_format(&self) -> u32 {
+        match self {
+            TextureFormat::Rgba8 => WebGl2RenderingContext::RGBA8,
+            TextureFormat::Srgb8Alpha8 => WebGl2RenderingContext::SRGB8_ALPHA8,
+            TextureFormat::R8 => WebGl2RenderingContext::R8,
+            TextureFormat::R32F => WebGl2RenderingContext::R32F,
+            TextureFormat::Rgba16F => WebGl2RenderingContext::RGBA16F,
+            TextureFormat::Rgba32F => WebGl2RenderingContext::RGBA32F,
+            TextureFormat::Rg32UI => WebGl2RenderingContext::RG32UI,
+            TextureFormat::Depth24 => WebGl2RenderingContext::DEPTH_COMPONENT24,
+        }
+    }
+
+    pub(super) fn format(&self) -> u32 {
+        match self {
+            TextureFormat::R8 | TextureFormat::R32F => WebGl2RenderingContext::RED,
+            TextureFormat::Rg32UI => WebGl2RenderingContext::RG_INTEGER,
+            TextureFormat::Depth24 => WebGl2RenderingContext::DEPTH_COMPONENT,
+            _ => WebGl2RenderingContext::RGBA,
+        }
+    }
+
+    pub(super) fn data_type(&self) -> u32 {
+        match self {
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 | TextureFormat::R8 => {
+                WebGl2RenderingContext::UNSIGNED_BYTE
+            }
+            TextureFormat::R32F | TextureFormat::Rgba16F | TextureFormat::Rgba32F => {
+                WebGl2RenderingContext::FLOAT
+            }
+            TextureFormat::Rg32UI | TextureFormat::Depth24 => WebGl2RenderingContext::UNSIGNED_INT,
+        }
+    }
+
+    fn channels(&self) -> usize {
+        match self {
+            TextureFormat::R8 | TextureFormat::R32F | TextureFormat::Depth24 => 1,
+            TextureFormat::Rg32UI => 2,
+            _ => 4,
+        }
+    }
+
+    fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::R32F => 4,
+            TextureFormat::Rgba8 | TextureFormat::Srgb8Alpha8 => 4,
+            TextureFormat::Rgba16F | TextureFormat::Rg32UI => 8,
+            TextureFormat::Rgba32F => 16,
+            TextureFormat::Depth24 => 4,
+        }
+    }
+
+    pub(super) fn is_float(&self) -> bool {
+        matches!(
+            self,
+            TextureFormat::R32F | TextureFormat::Rgba16F | TextureFormat::Rgba32F
+        )
+    }
+
+    pub(super) fn is_integer(&self) -> bool {
+        *self == TextureFormat::Rg32UI
+    }
+
+    // 32 bit float textures need OES_texture_float_linear to be filtered
+    fn is_filterable(&self) -> bool {
+        !matches!(
+            self,
+            TextureFormat::R32F
+                | TextureFormat::Rgba32F
+                | TextureFormat::Rg32UI
+                | TextureFormat::Depth24
+        )
+    }
+
+    pub(super) fn is_depth(&self) -> bool {
+        *self == TextureFormat::Depth24
+    }
+}
+
+impl Filter {
+    fn gl_enum(&self) -> u32 {
+        match self {
+            Filter::Nearest => WebGl2RenderingContext::NEAREST,
+            Filter::Linear => WebGl2RenderingContext::LINEAR,
+        }
+    }
+}
+
+impl Wrap {
+    fn gl_enum(&self) -> u32 {
+        match self {
+            Wrap::Repeat => WebGl2RenderingContext::REPEAT,
+            Wrap::MirroredRepeat => WebGl2RenderingContext::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => WebGl2RenderingContext::CLAMP_TO_EDGE,
+        }
+    }
+}
+
+impl Drop for SharedTexture {
+    fn drop(&mut self) {
+        self.context.delete_texture(Some(&self.texture.borrow()));
+    }
+}
@@ -1,13 +1,31 @@
+use std::{cell::RefCell, rc::Rc};
+
 use web_sys::*;
 
-use super::buffer::GlBuffer;
+use super::{
+    buffer::{GlBuffer, SharedBuffer},
+    context::GlContext,
+};
+use crate::error::Error;
 
 pub struct GlVAO {
-    context: WebGl2RenderingContext,
-    vao: WebGlVertexArrayObject,
+    shared: Rc<SharedVAO>,
+}
+
+struct SharedVAO {
+    context: GlContext,
+    vao: RefCell<WebGlVertexArrayObject>,
+    /// Buffers linked so far, linked again after a context loss
+    links: RefCell<Vec<Link>>,
 }
 
-#[derive(Debug)]
+struct Link {
+    buffer: Rc<SharedBuffer>,
+    attributes: Vec<Attrib>,
+    instanced: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub enum Attrib {
     VecF32(u32, i32),      // location, vec len
     VecI32(u32, i32),      // location, vec len
@@ -17,35 +35,53 @@ pub enum Attrib {
 
 impl GlVAO {
     pub fn bind(&self) {
-        self.context.bind_vertex_array(Some(&self.vao));
+        let shared = &self.shared;
+        shared.context.bind_vertex_array(Some(&shared.vao.borrow()));
     }
-    pub fn new(context: &WebGl2RenderingContext) -> Result<GlVAO, String> {
-        Ok(Self {
-            vao: context.create_vertex_array().ok_or("Couldn't create vao")?,
-            context: context.clone(),
-        })
+    pub(super) fn new(context: &GlContext) -> Result<GlVAO, Error> {
+        let vao = GlVAO {
+            shared: Rc::new(SharedVAO {
+                vao: RefCell::new(create_vao(context)?),
+                context: context.clone(),
+                links: RefCell::new(vec![]),
+            }),
+        };
+        context.register(&vao.shared, |shared| {
+            *shared.vao.borrow_mut() = create_vao(&shared.context)?;
+            Ok(())
+        });
+        context.register_link(&vao.shared, |shared| {
+            let vao = GlVAO { shared };
+            for link in vao.shared.links.borrow().iter() {
+                let buffer = GlBuffer {
+                    shared: link.buffer.clone(),
+                };
+                vao.link(&buffer, &link.attributes, link.instanced);
+            }
+            Ok(())
+        });
+        Ok(vao)
     }
 
     pub fn link_instance_buffer(&self, buffer: &GlBuffer, attributes: &[Attrib]) {
-        self.context.bind_vertex_array(Some(&self.vao));
-        buffer.bind();
-
-        let mut stride = 0;
-        for attrib in attributes {
-            stride += attrib.bytes_count();
-        }
-
-        let mut offset = 0;
-        for attrib in attributes {
-            attrib.vertex_attrib_pointer(&self.context, stride, offset);
-            attrib.vertex_attrib_divisor(&self.context, 1);
-            attrib.enable_vertex_attrib_array(&self.context);
-
-            offset += attrib.bytes_count();
-        }
+        self.add_link(buffer, attributes, true);
     }
     pub fn link_buffer(&self, buffer: &GlBuffer, attributes: &[Attrib]) {
-        self.context.bind_vertex_array(Some(&self.vao));
+        self.add_link(buffer, attributes, false);
+    }
+
+    fn add_link(&self, buffer: &GlBuffer, attributes: &[Attrib], instanced: bool) {
+        self.link(buffer, attributes, instanced);
+        self.shared.links.borrow_mut().push(Link {
+            buffer: buffer.shared.clone(),
+            attributes: attributes.to_vec(),
+            instanced,
+        });
+    }
+
+    fn link(&self, buffer: &GlBuffer, attributes: &[Attrib], instanced: bool) {
+        let context = &self.shared.context;
+        self.bind();
         buffer.bind();
 
         let mut stride = 0;
@@ -55,14 +91,23 @@ impl GlVAO {
 
         let mut offset = 0;
         for attrib in attributes {
-            attrib.vertex_attrib_pointer(&self.context, stride, offset);
-            attrib.enable_vertex_attrib_array(&self.context);
+            attrib.vertex_attrib_pointer(context, stride, offset);
+            if instanced {
+                attrib.vertex_attrib_divisor(context, 1);
+            }
+            attrib.enable_vertex_attrib_array(context);
 
             offset += attrib.bytes_count();
         }
     }
 }
 
+fn create_vao(context: &WebGl2RenderingContext) -> Result<WebGlVertexArrayObject, Error> {
+    context
+        .create_vertex_array()
+        .ok_or_else(|| Error::Allocation(String::from("Couldn't create vao")))
+}
+
 impl Attrib {
     fn bytes_count(&self) -> i32 {
         match self {
@@ -133,8 +178,8 @@ impl Attrib {
     }
 }
 
-impl Drop for GlVAO {
+impl Drop for SharedVAO {
     fn drop(&mut self) {
-        self.context.delete_vertex_array(Some(&self.vao));
+        self.context.delete_vertex_array(Some(&self.vao.borrow()));
     }
 }
\ No newline at end of file
@@ -0,0 +1,490 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use wasm_bindgen::{prelude::*, JsCast};
+use web_sys::*;
+
+use crate::{error::Error, gl::Camera2D};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    Other(i16),
+}
+
+impl MouseButton {
+    fn from_button(button: i16) -> Self {
+        match button {
+            0 => MouseButton::Left,
+            1 => MouseButton::Middle,
+            2 => MouseButton::Right,
+            other => MouseButton::Other(other),
+        }
+    }
+}
+
+/// Change of the touches since the previous frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gesture {
+    /// Ratio between the current and previous distance of the touches, 1 without pinch
+    pub pinch: f32,
+    /// Movement of the center of the touches in canvas pixels
+    pub pan: [f32; 2],
+    /// Center of the touches in canvas pixels
+    pub center: [f32; 2],
+}
+
+impl Default for Gesture {
+    fn default() -> Self {
+        Self {
+            pinch: 1.,
+            pan: [0., 0.],
+            center: [0., 0.],
+        }
+    }
+}
+
+enum InputEvent {
+    KeyDown(String),
+    KeyUp(String),
+    PointerDown {
+        id: i32,
+        button: i16,
+        position: [f32; 2],
+        touch: bool,
+    },
+    PointerMove {
+        id: i32,
+        position: [f32; 2],
+        primary: bool,
+        touch: bool,
+    },
+    PointerUp {
+        id: i32,
+        button: i16,
+        position: [f32; 2],
+        touch: bool,
+    },
+    Wheel([f32; 2]),
+    /// The page lost the focus, keys and buttons are released
+    Blur,
+}
+
+type Listener = (EventTarget, &'static str, Closure<dyn FnMut(Event)>);
+
+/// Buffers the canvas input events and exposes their state once per frame.
+/// Positions are in canvas pixels from the top left corner.
+///
+/// ```ignore
+/// input.update();
+/// if input.key_pressed("Space") { ... }
+/// camera.pan(input.gesture().pan, gl.canvas_size());
+/// ```
+pub struct Input {
+    events: Rc<RefCell<Vec<InputEvent>>>,
+    listeners: Vec<Listener>,
+    keys: HashSet<String>,
+    keys_pressed: HashSet<String>,
+    keys_released: HashSet<String>,
+    buttons: HashSet<MouseButton>,
+    buttons_pressed: HashSet<MouseButton>,
+    buttons_released: HashSet<MouseButton>,
+    pointer: [f32; 2],
+    pointer_delta: [f32; 2],
+    wheel: [f32; 2],
+    touches: HashMap<i32, [f32; 2]>,
+    gesture: Gesture,
+}
+
+impl Input {
+    pub fn new(canvas: &HtmlCanvasElement) -> Result<Input, Error> {
+        let window =
+            web_sys::window().ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?;
+        canvas
+            .style()
+            .set_property("touch-action", "none")
+            .map_err(Error::js("Couldn't disable touch actions"))?;
+
+        let mut input = Input {
+            events: Rc::new(RefCell::new(vec![])),
+            listeners: vec![],
+            keys: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            keys_released: HashSet::new(),
+            buttons: HashSet::new(),
+            buttons_pressed: HashSet::new(),
+            buttons_released: HashSet::new(),
+            pointer: [0., 0.],
+            pointer_delta: [0., 0.],
+            wheel: [0., 0.],
+            touches: HashMap::new(),
+            gesture: Gesture::default(),
+        };
+
+        input.listen(&window, "keydown", |event| {
+            let event = event.dyn_ref::<KeyboardEvent>()?;
+            (!event.repeat()).then(|| InputEvent::KeyDown(event.code()))
+        })?;
+        input.listen(&window, "keyup", |event| {
+            let event = event.dyn_ref::<KeyboardEvent>()?;
+            Some(InputEvent::KeyUp(event.code()))
+        })?;
+        input.listen(&window, "blur", |_| Some(InputEvent::Blur))?;
+
+        let target = canvas.clone();
+        input.listen(canvas, "pointerdown", move |event| {
+            let event = event.dyn_ref::<PointerEvent>()?;
+            let _ = target.set_pointer_capture(event.pointer_id());
+            Some(InputEvent::PointerDown {
+                id: event.pointer_id(),
+                button: event.button(),
+                position: canvas_position(&target, event),
+                touch: event.pointer_type() == "touch",
+            })
+        })?;
+        let target = canvas.clone();
+        input.listen(canvas, "pointermove", move |event| {
+            let event = event.dyn_ref::<PointerEvent>()?;
+            Some(InputEvent::PointerMove {
+                id: event.pointer_id(),
+                position: canvas_position(&target, event),
+                primary: event.is_primary(),
+                touch: event.pointer_type() == "touch",
+            })
+        })?;
+        for name in ["pointerup", "pointercancel"] {
+            let target = canvas.clone();
+            input.listen(canvas, name, move |event| {
+                let event = event.dyn_ref::<PointerEvent>()?;
+                Some(InputEvent::PointerUp {
+                    id: event.pointer_id(),
+                    button: event.button(),
+                    position: canvas_position(&target, event),
+                    touch: event.pointer_type() == "touch",
+                })
+            })?;
+        }
+
+        let target = canvas.clone();
+        input.listen(canvas, "wheel", move |event| {
+            event.prevent_default();
+            let event = event.dyn_ref::<WheelEvent>()?;
+            let scale = match event.delta_mode() {
+                WheelEvent::DOM_DELTA_LINE => 16.,
+                WheelEvent::DOM_DELTA_PAGE => target.client_height() as f32,
+                _ => 1.,
+            } * pixel_ratio(&target);
+            Some(InputEvent::Wheel([
+                event.delta_x() as f32 * scale,
+                event.delta_y() as f32 * scale,
+            ]))
+        })?;
+        input.listen(canvas, "contextmenu", |event| {
+            event.prevent_default();
+            None
+        })?;
+
+        Ok(input)
+    }
+
+    /// Applies the events received since the last update, should be called at the
+    /// start of every frame
+    pub fn update(&mut self) {
+        self.keys_pressed.clear();
+        self.keys_released.clear();
+        self.buttons_pressed.clear();
+        self.buttons_released.clear();
+        self.pointer_delta = [0., 0.];
+        self.wheel = [0., 0.];
+
+        let previous_touches = self.touches.clone();
+        let events: Vec<InputEvent> = self.events.borrow_mut().drain(..).collect();
+        for event in events {
+            match event {
+                InputEvent::KeyDown(code) => {
+                    if self.keys.insert(code.clone()) {
+                        self.keys_pressed.insert(code);
+                    }
+                }
+                InputEvent::KeyUp(code) => {
+                    if self.keys.remove(&code) {
+                        self.keys_released.insert(code);
+                    }
+                }
+                InputEvent::PointerDown {
+                    id,
+                    button,
+                    position,
+                    touch,
+                } => {
+                    if touch {
+                        self.touches.insert(id, position);
+                    } else {
+                        let button = MouseButton::from_button(button);
+                        self.buttons.insert(button);
+                        self.buttons_pressed.insert(button);
+                    }
+                    self.pointer = position;
+                }
+                InputEvent::PointerMove {
+                    id,
+                    position,
+                    primary,
+                    touch,
+                } => {
+                    if touch {
+                        if let Some(touch) = self.touches.get_mut(&id) {
+                            *touch = position;
+                        }
+                    }
+                    if primary {
+                        self.pointer_delta[0] += position[0] - self.pointer[0];
+                        self.pointer_delta[1] += position[1] - self.pointer[1];
+                        self.pointer = position;
+                    }
+                }
+                InputEvent::PointerUp {
+                    id,
+                    button,
+                    position,
+                    touch,
+                } => {
+                    if touch {
+                        self.touches.remove(&id);
+                    } else {
+                        let button = MouseButton::from_button(button);
+                        if self.buttons.remove(&button) {
+                            self.buttons_released.insert(button);
+                        }
+                    }
+                    self.pointer = position;
+                }
+                InputEvent::Wheel(delta) => {
+                    self.wheel[0] += delta[0];
+                    self.wheel[1] += delta[1];
+                }
+                InputEvent::Blur => {
+                    self.keys_released.extend(self.keys.drain());
+                    self.buttons_released.extend(self.buttons.drain());
+                    self.touches.clear();
+                }
+            }
+        }
+
+        self.gesture = gesture(&previous_touches, &self.touches);
+    }
+
+    /// `code` is a physical key as in `KeyboardEvent.code`, like "KeyW" or "Space"
+    pub fn key_down(&self, code: &str) -> bool {
+        self.keys.contains(code)
+    }
+
+    /// The key went down during the last frame
+    pub fn key_pressed(&self, code: &str) -> bool {
+        self.keys_pressed.contains(code)
+    }
+
+    pub fn key_released(&self, code: &str) -> bool {
+        self.keys_released.contains(code)
+    }
+
+    pub fn button_down(&self, button: MouseButton) -> bool {
+        self.buttons.contains(&button)
+    }
+
+    pub fn button_pressed(&self, button: MouseButton) -> bool {
+        self.buttons_pressed.contains(&button)
+    }
+
+    pub fn button_released(&self, button: MouseButton) -> bool {
+        self.buttons_released.contains(&button)
+    }
+
+    /// Last position of the mouse or primary touch
+    pub fn pointer_position(&self) -> [f32; 2] {
+        self.pointer
+    }
+
+    pub fn pointer_delta(&self) -> [f32; 2] {
+        self.pointer_delta
+    }
+
+    /// `viewport` is the canvas size, see `Gl::canvas_size`
+    pub fn pointer_world(&self, camera: &Camera2D, viewport: (i32, i32)) -> [f32; 2] {
+        camera.screen_to_world(self.pointer, viewport)
+    }
+
+    /// Scroll since the last frame in canvas pixels
+    pub fn wheel_delta(&self) -> [f32; 2] {
+        self.wheel
+    }
+
+    pub fn touches(&self) -> impl Iterator<Item = [f32; 2]> + '_ {
+        self.touches.values().copied()
+    }
+
+    pub fn gesture(&self) -> Gesture {
+        self.gesture
+    }
+
+    fn listen<F>(
+        &mut self,
+        target: &EventTarget,
+        name: &'static str,
+        mut to_event: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(&Event) -> Option<InputEvent> + 'static,
+    {
+        let events = self.events.clone();
+        let closure = Closure::wrap(Box::new(move |event: Event| {
+            if let Some(event) = to_event(&event) {
+                events.borrow_mut().push(event);
+            }
+        }) as Box<dyn FnMut(Event)>);
+
+        target
+            .add_event_listener_with_callback(name, closure.as_ref().unchecked_ref())
+            .map_err(Error::js(&format!("Couldn't listen to {}", name)))?;
+        self.listeners.push((target.clone(), name, closure));
+        Ok(())
+    }
+}
+
+impl Drop for Input {
+    fn drop(&mut self) {
+        for (target, name, closure) in &self.listeners {
+            let _ =
+                target.remove_event_listener_with_callback(name, closure.as_ref().unchecked_ref());
+        }
+    }
+}
+
+/// Canvas pixels per CSS pixel
+fn pixel_ratio(canvas: &HtmlCanvasElement) -> f32 {
+    match canvas.client_width() {
+        0 => 1.,
+        client_width => canvas.width() as f32 / client_width as f32,
+    }
+}
+
+fn canvas_position(canvas: &HtmlCanvasElement, event: &MouseEvent) -> [f32; 2] {
+    let ratio = pixel_ratio(canvas);
+    [
+        event.offset_x() as f32 * ratio,
+        event.offset_y() as f32 * ratio,
+    ]
+}
+
+fn gesture(previous: &HashMap<i32, [f32; 2]>, current: &HashMap<i32, [f32; 2]>) -> Gesture {
+    let moved: Vec<([f32; 2], [f32; 2])> = current
+        .iter()
+        .filter_map(|(id, &position)| Some((previous.get(id).copied()?, position)))
+        .collect();
+    if moved.is_empty() {
+        return Gesture {
+            center: center(current.values().copied()),
+            ..Gesture::default()
+        };
+    }
+
+    let previous_center = center(moved.iter().map(|(previous, _)| *previous));
+    let current_center = center(moved.iter().map(|(_, current)| *current));
+    let spread = |center: [f32; 2], points: &mut dyn Iterator<Item = [f32; 2]>| {
+        points
+            .map(|p| ((p[0] - center[0]).powi(2) + (p[1] - center[1]).powi(2)).sqrt())
+            .sum::<f32>()
+    };
+    let previous_spread = spread(previous_center, &mut moved.iter().map(|(p, _)| *p));
+    let current_spread = spread(current_center, &mut moved.iter().map(|(_, c)| *c));
+
+    Gesture {
+        pinch: if moved.len() > 1 && previous_spread > 0. {
+            current_spread / previous_spread
+        } else {
+            1.
+        },
+        pan: [
+            current_center[0] - previous_center[0],
+            current_center[1] - previous_center[1],
+        ],
+        center: current_center,
+    }
+}
+
+fn center(points: impl Iterator<Item = [f32; 2]>) -> [f32; 2] {
+    let (sum, count) = points.fold(([0., 0.], 0), |(sum, count), p| {
+        ([sum[0] + p[0], sum[1] + p[1]], count + 1)
+    });
+    if count == 0 {
+        [0., 0.]
+    } else {
+        [sum[0] / count as f32, sum[1] / count as f32]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touches(points: &[(i32, [f32; 2])]) -> HashMap<i32, [f32; 2]> {
+        points.iter().copied().collect()
+    }
+
+    fn assert_close(value: [f32; 2], expected: [f32; 2]) {
+        assert!(
+            (value[0] - expected[0]).abs() < 1e-4 && (value[1] - expected[1]).abs() < 1e-4,
+            "expected {:?} but found {:?}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn pinch_is_the_ratio_of_the_spreads() {
+        let previous = touches(&[(1, [90., 100.]), (2, [110., 100.])]);
+        let current = touches(&[(1, [70., 100.]), (2, [130., 100.])]);
+        let gesture = gesture(&previous, &current);
+        assert!((gesture.pinch - 3.).abs() < 1e-4, "{}", gesture.pinch);
+        assert_close(gesture.pan, [0., 0.]);
+        assert_close(gesture.center, [100., 100.]);
+    }
+
+    #[test]
+    fn pan_follows_the_center_of_the_touches() {
+        let previous = touches(&[(1, [0., 0.]), (2, [20., 0.])]);
+        let current = touches(&[(1, [5., 8.]), (2, [25., 8.]), (3, [500., 500.])]);
+        let gesture = gesture(&previous, &current);
+        assert!((gesture.pinch - 1.).abs() < 1e-4, "{}", gesture.pinch);
+        assert_close(gesture.pan, [5., 8.]);
+        assert_close(gesture.center, [15., 8.]);
+    }
+
+    #[test]
+    fn a_single_touch_pans_without_pinching() {
+        let moved = gesture(&touches(&[(7, [10., 10.])]), &touches(&[(7, [13., 6.])]));
+        assert_eq!(moved.pinch, 1.);
+        assert_close(moved.pan, [3., -4.]);
+        assert_close(moved.center, [13., 6.]);
+
+        let started = gesture(&HashMap::new(), &touches(&[(1, [40., 20.])]));
+        assert_eq!(
+            started,
+            Gesture {
+                center: [40., 20.],
+                ..Gesture::default()
+            }
+        );
+    }
+
+    #[test]
+    fn center_is_the_average_and_the_origin_without_points() {
+        assert_close(center([[0., 0.], [4., 2.], [2., 7.]].into_iter()), [2., 3.]);
+        assert_close(center(std::iter::empty()), [0., 0.]);
+    }
+}
@@ -1,46 +1,83 @@
-use crate::error;
+use crate::{
+    clock::*,
+    error::{self, Error},
+    gl::{CanvasSize, ContextStatus, Gl},
+    trace,
+};
 use std::{cell::RefCell, rc::Rc};
-use wasm_bindgen::{prelude::*, JsCast};
 use web_sys::window;
 
-fn request_animation_frame(f: &Closure<dyn FnMut()>) -> Result<(), String> {
-    web_sys::window()
-        .ok_or("Couldn't get window")?
-        .request_animation_frame(f.as_ref().unchecked_ref())
-        .map_err(|e| error::fmt(e, "Request animation frame failed"))?;
-    Ok(())
-}
-pub fn get_current_time() -> Result<f32, String> {
+pub fn get_current_time() -> Result<f64, Error> {
     Ok(window()
-        .ok_or("Couldn't get window")?
+        .ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?
         .performance()
-        .ok_or("Couldn't get window.performance")?
-        .now() as f32
+        .ok_or_else(|| Error::Dom(String::from("Couldn't get window.performance")))?
+        .now()
         / 1000.)
 }
 
-pub fn create_draw_loop<F>(mut draw_loop: F) -> Result<(), String>
+/// Calls `draw_loop` on every animation frame until the returned handle stops it.
+/// Dropping the handle keeps the loop running.
+pub fn create_draw_loop<F>(draw_loop: F) -> Result<LoopHandle, Error>
+where
+    F: FnMut(FrameTime) -> Result<(), Error> + 'static,
+{
+    create_draw_loop_with(BrowserClock, BrowserScheduler::new(), draw_loop)
+}
+
+/// `create_draw_loop` reading the time from `clock` and running the frames
+/// with `scheduler`, e.g. `ManualClock` and `ManualScheduler` outside of a browser
+pub fn create_draw_loop_with<F>(
+    clock: impl Clock + 'static,
+    scheduler: impl Scheduler + 'static,
+    mut draw_loop: F,
+) -> Result<LoopHandle, Error>
 where
-    F: FnMut(FrameTime) -> Result<(), String> + 'static,
+    F: FnMut(FrameTime) -> Result<(), Error> + 'static,
 {
-    let f = Rc::new(RefCell::new(None));
-    let g = f.clone();
+    let handle = LoopHandle {
+        state: Rc::new(RefCell::new(LoopState {
+            scheduler: Box::new(scheduler),
+            frame: None,
+            frame_request: None,
+            paused: false,
+            steps: 0,
+            stop_on_error: false,
+            reuse_delta: false,
+        })),
+    };
+    let state = handle.state.clone();
 
     let mut frame_count = 0;
-    let mut past_time = get_current_time()?;
+    let mut past_time = clock.now()?;
+    let mut delta_time = 0.;
     let mut render_time = 0.;
 
     let mut render_time_store = [0.; 30];
     let mut render_average = 0.;
     let mut render_time_store_index = 0;
 
-    *g.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+    let frame = RefCell::new(move || {
+        {
+            let mut state = state.borrow_mut();
+            state.frame_request = None;
+            if state.paused {
+                if state.steps == 0 {
+                    return;
+                }
+                state.steps -= 1;
+            }
+        }
         frame_count += 1;
 
-        error::report((|| {
-            let start_time = get_current_time()?;
-            let delta_time = start_time - past_time;
+        let result = (|| {
+            let start_time = clock.now()?;
+            // The time spent paused doesn't count
+            if !std::mem::take(&mut state.borrow_mut().reuse_delta) {
+                delta_time = (start_time - past_time) as f32;
+            }
 
+            let frame_span = trace::span!("frame");
             draw_loop(FrameTime {
                 fps: 1. / delta_time,
                 delta: delta_time,
@@ -48,9 +85,11 @@ where
                 render: render_time,
                 render_average,
                 seconds: start_time,
+                size: None,
             })?;
+            drop(frame_span);
 
-            render_time = get_current_time()? - start_time;
+            render_time = (clock.now()? - start_time) as f32;
             past_time = start_time;
 
             render_time_store[render_time_store_index] = render_time;
@@ -58,23 +97,426 @@ where
             if render_time_store_index >= render_time_store.len() {
                 render_time_store_index = 0;
             }
-            
+
+            // Summed again every frame, a running sum would drift with the rounding errors
             render_average = render_time_store.iter().sum::<f32>()
                 / render_time_store.len().min(frame_count as usize) as f32;
-                
-            request_animation_frame(f.borrow().as_ref().unwrap())
-        })());
-    }) as Box<dyn FnMut()>));
+            Ok(())
+        })();
+        trace::end_frame();
+
+        let handle = LoopHandle {
+            state: state.clone(),
+        };
+        if result.is_err() && state.borrow().stop_on_error {
+            error::report(result);
+            error::report(handle.stop());
+        } else {
+            error::report(result);
+            error::report(handle.request_frame());
+        }
+    });
+
+    // Borrowed mutably while running, a frame can't run itself
+    handle.state.borrow_mut().frame = Some(Rc::new(move || (frame.borrow_mut())()));
+    handle
+        .request_frame()
+        .map_err(|e| e.context("Couldn't start the draw loop"))?;
+    Ok(handle)
+}
+
+/// `create_draw_loop` resizing `gl` at the start of every frame, the callback
+/// gets the new size in `FrameTime::size`. The loop is paused while the
+/// context is lost.
+pub fn create_canvas_loop<F>(gl: Gl, draw_loop: F) -> Result<LoopHandle, Error>
+where
+    F: FnMut(&mut Gl, FrameTime) -> Result<(), Error> + 'static,
+{
+    create_canvas_loop_with(BrowserClock, BrowserScheduler::new(), gl, draw_loop)
+}
+
+/// `create_canvas_loop` with the time source of `create_draw_loop_with`
+pub fn create_canvas_loop_with<F>(
+    clock: impl Clock + 'static,
+    scheduler: impl Scheduler + 'static,
+    mut gl: Gl,
+    mut draw_loop: F,
+) -> Result<LoopHandle, Error>
+where
+    F: FnMut(&mut Gl, FrameTime) -> Result<(), Error> + 'static,
+{
+    let handle: Rc<RefCell<Option<LoopHandle>>> = Rc::default();
+    let frame_handle = handle.clone();
+    let created = create_draw_loop_with(clock, scheduler, move |mut time| {
+        if gl.update_context()? == ContextStatus::Lost {
+            if let Some(handle) = frame_handle.borrow().clone() {
+                handle.pause()?;
+                gl.on_context_restored(move || error::report(handle.resume()));
+            }
+            return Ok(());
+        }
+        time.size = Some(gl.update_size()?);
+        draw_loop(&mut gl, time)
+    })?;
+    *handle.borrow_mut() = Some(created.clone());
+    Ok(created)
+}
+
+/// Simulation rate of `create_fixed_loop`
+#[derive(Debug, Clone, Copy)]
+pub struct FixedStep {
+    /// Seconds simulated by each update
+    pub dt: f32,
+    /// Updates per frame at most, the rest of a long frame is dropped so a slow
+    /// update can't fall further behind every frame
+    pub max_steps: u32,
+}
+
+impl Default for FixedStep {
+    fn default() -> Self {
+        Self {
+            dt: 1. / 60.,
+            max_steps: 5,
+        }
+    }
+}
+
+/// Runs `update` at the fixed rate of `step` whatever the refresh rate, and
+/// `render` once per frame. `render` gets how far the time is between the last
+/// update and the next one, from 0 to 1, to interpolate between the two states.
+/// Both callbacks share `state`.
+pub fn create_fixed_loop<S, U, R>(
+    step: FixedStep,
+    state: S,
+    update: U,
+    render: R,
+) -> Result<LoopHandle, Error>
+where
+    S: 'static,
+    U: FnMut(&mut S, f32) -> Result<(), Error> + 'static,
+    R: FnMut(&mut S, FrameTime, f32) -> Result<(), Error> + 'static,
+{
+    create_fixed_loop_with(
+        BrowserClock,
+        BrowserScheduler::new(),
+        step,
+        state,
+        update,
+        render,
+    )
+}
+
+/// `create_fixed_loop` with the time source of `create_draw_loop_with`
+pub fn create_fixed_loop_with<S, U, R>(
+    clock: impl Clock + 'static,
+    scheduler: impl Scheduler + 'static,
+    step: FixedStep,
+    mut state: S,
+    mut update: U,
+    mut render: R,
+) -> Result<LoopHandle, Error>
+where
+    S: 'static,
+    U: FnMut(&mut S, f32) -> Result<(), Error> + 'static,
+    R: FnMut(&mut S, FrameTime, f32) -> Result<(), Error> + 'static,
+{
+    if step.dt <= 0. {
+        return Err(Error::Invalid(format!(
+            "Expected a positive time step but found {}",
+            step.dt
+        )));
+    }
+
+    let mut accumulator = 0.;
+    create_draw_loop_with(clock, scheduler, move |time| {
+        accumulator += time.delta.max(0.);
+
+        let mut steps = 0;
+        while accumulator >= step.dt {
+            if steps == step.max_steps {
+                accumulator %= step.dt;
+                break;
+            }
+            update(&mut state, step.dt)?;
+            accumulator -= step.dt;
+            steps += 1;
+        }
 
-    request_animation_frame(g.borrow().as_ref().unwrap())?;
-    Ok(())
+        render(&mut state, time, accumulator / step.dt)
+    })
+}
+
+/// Controls a loop created by `create_draw_loop`, can be cloned into other callbacks
+#[derive(Clone)]
+pub struct LoopHandle {
+    state: Rc<RefCell<LoopState>>,
+}
+
+struct LoopState {
+    scheduler: Box<dyn Scheduler>,
+    /// Holds the state of the loop and its captured resources until `stop`
+    frame: Option<Frame>,
+    frame_request: Option<i32>,
+    paused: bool,
+    /// Frames left to run while paused
+    steps: u32,
+    stop_on_error: bool,
+    /// Set when resuming or stepping so the next frame repeats the last delta
+    reuse_delta: bool,
+}
+
+impl LoopHandle {
+    pub fn pause(&self) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        state.paused = true;
+        state.steps = 0;
+        if let Some(id) = state.frame_request.take() {
+            state.scheduler.cancel(id)?;
+        }
+        Ok(())
+    }
+
+    pub fn resume(&self) -> Result<(), Error> {
+        {
+            let mut state = self.state.borrow_mut();
+            if !state.paused {
+                return Ok(());
+            }
+            state.paused = false;
+            state.reuse_delta = true;
+        }
+        self.request_frame()
+    }
+
+    /// Runs a single frame while paused, ignored otherwise
+    pub fn step(&self) -> Result<(), Error> {
+        {
+            let mut state = self.state.borrow_mut();
+            if !state.paused {
+                return Ok(());
+            }
+            state.steps += 1;
+            state.reuse_delta = true;
+        }
+        self.request_frame()
+    }
+
+    /// Ends the loop for good, dropping the callback and everything it captured.
+    /// Can be called from the callback itself.
+    pub fn stop(&self) -> Result<(), Error> {
+        let (frame, cancelled) = {
+            let mut state = self.state.borrow_mut();
+            let cancelled = match state.frame_request.take() {
+                Some(id) => state.scheduler.cancel(id),
+                None => Ok(()),
+            };
+            (state.frame.take(), cancelled)
+        };
+        // Dropped outside of the borrow, its captures may hold other handles
+        drop(frame);
+        cancelled
+    }
+
+    /// By default errors returned by the callback are reported and the loop continues
+    pub fn set_stop_on_error(&self, stop_on_error: bool) {
+        self.state.borrow_mut().stop_on_error = stop_on_error;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.borrow().paused
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.state.borrow().frame.is_none()
+    }
+
+    fn request_frame(&self) -> Result<(), Error> {
+        let mut state = self.state.borrow_mut();
+        if state.frame_request.is_some() || (state.paused && state.steps == 0) {
+            return Ok(());
+        }
+        let frame = match &state.frame {
+            Some(frame) => frame.clone(),
+            None => return Ok(()),
+        };
+        let id = state.scheduler.request(frame)?;
+        state.frame_request = Some(id);
+        Ok(())
+    }
 }
 
 pub struct FrameTime {
     pub fps: f32,
-    pub seconds: f32,
+    /// Time of the loop clock when the frame began
+    pub seconds: f64,
     pub render: f32,
     pub render_average: f32,
     pub delta: f32,
     pub frame_count: i32,
+    /// Size of the canvas for this frame, only set by `create_canvas_loop`
+    pub size: Option<CanvasSize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame times seen by the callback, which spends `work` seconds of `clock` each frame
+    fn recorded_loop(
+        clock: &ManualClock,
+        scheduler: &ManualScheduler,
+        work: f64,
+    ) -> (LoopHandle, Rc<RefCell<Vec<FrameTime>>>) {
+        let times: Rc<RefCell<Vec<FrameTime>>> = Rc::default();
+        let handle = {
+            let (clock, times) = (clock.clone(), times.clone());
+            create_draw_loop_with(clock.clone(), scheduler.clone(), move |time| {
+                times.borrow_mut().push(time);
+                clock.advance(work);
+                Ok(())
+            })
+            .unwrap()
+        };
+        (handle, times)
+    }
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-5,
+            "expected {} but found {}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn frame_times_follow_the_clock() {
+        let clock = ManualClock::new(1000.);
+        let scheduler = ManualScheduler::default();
+        let (_handle, times) = recorded_loop(&clock, &scheduler, 0.004);
+
+        for wait in [0.016, 0.016, 0.026] {
+            clock.advance(wait);
+            assert!(scheduler.run_frame());
+        }
+
+        let times = times.borrow();
+        let deltas: Vec<f32> = times.iter().map(|time| time.delta).collect();
+        assert_close(deltas[0], 0.016);
+        assert_close(deltas[1], 0.020);
+        assert_close(deltas[2], 0.030);
+        assert_close(times[2].fps, 1. / 0.030);
+        assert_eq!(times[2].frame_count, 3);
+        assert!((times[2].seconds - 1000.066).abs() < 1e-9);
+        assert_close(times[0].render_average, 0.);
+        assert_close(times[1].render, 0.004);
+        assert_close(times[2].render_average, 0.004);
+    }
+
+    #[test]
+    fn paused_time_is_skipped() {
+        let clock = ManualClock::new(0.);
+        let scheduler = ManualScheduler::default();
+        let (handle, times) = recorded_loop(&clock, &scheduler, 0.);
+
+        clock.advance(0.01);
+        assert!(scheduler.run_frame());
+        handle.pause().unwrap();
+        assert!(handle.is_paused());
+        assert!(!scheduler.run_frame());
+
+        clock.advance(5.);
+        handle.step().unwrap();
+        assert!(scheduler.run_frame());
+        assert!(!scheduler.run_frame());
+
+        clock.advance(5.);
+        handle.resume().unwrap();
+        assert!(scheduler.run_frame());
+        clock.advance(0.02);
+        assert!(scheduler.run_frame());
+
+        let deltas: Vec<f32> = times.borrow().iter().map(|time| time.delta).collect();
+        assert_eq!(deltas.len(), 4);
+        assert_close(deltas[1], 0.01);
+        assert_close(deltas[2], 0.01);
+        assert_close(deltas[3], 0.02);
+    }
+
+    #[test]
+    fn stop_drops_the_callback() {
+        let clock = ManualClock::new(0.);
+        let scheduler = ManualScheduler::default();
+        let (handle, times) = recorded_loop(&clock, &scheduler, 0.);
+
+        assert!(scheduler.run_frame());
+        handle.stop().unwrap();
+        assert!(handle.is_stopped());
+        assert!(!scheduler.is_requested());
+        assert!(!scheduler.run_frame());
+        handle.resume().unwrap();
+        assert!(!scheduler.run_frame());
+        assert_eq!(times.borrow().len(), 1);
+        assert_eq!(Rc::strong_count(&times), 1);
+    }
+
+    #[test]
+    fn errors_stop_the_loop_when_asked() {
+        for stop_on_error in [false, true] {
+            let scheduler = ManualScheduler::default();
+            let handle = create_draw_loop_with(ManualClock::new(0.), scheduler.clone(), |_| {
+                Err(Error::Invalid(String::from("Frame failed")))
+            })
+            .unwrap();
+            handle.set_stop_on_error(stop_on_error);
+
+            assert!(scheduler.run_frame());
+            assert_eq!(handle.is_stopped(), stop_on_error);
+            assert_eq!(scheduler.is_requested(), !stop_on_error);
+        }
+    }
+
+    #[test]
+    fn fixed_loop_updates_at_the_step_rate() {
+        let clock = ManualClock::new(0.);
+        let scheduler = ManualScheduler::default();
+        let frames: Rc<RefCell<Vec<(u32, f32)>>> = Rc::default();
+        let step = FixedStep {
+            dt: 0.25,
+            max_steps: 3,
+        };
+        let _handle = {
+            let frames = frames.clone();
+            create_fixed_loop_with(
+                clock.clone(),
+                scheduler.clone(),
+                step,
+                0,
+                |updates: &mut u32, dt| {
+                    assert_eq!(dt, 0.25);
+                    *updates += 1;
+                    Ok(())
+                },
+                move |updates, _, alpha| {
+                    frames.borrow_mut().push((std::mem::take(updates), alpha));
+                    Ok(())
+                },
+            )
+            .unwrap()
+        };
+
+        // The last frame is longer than `max_steps` updates, its remainder is dropped
+        for wait in [0.6, 0.15, 0.1, 2.1] {
+            clock.advance(wait);
+            assert!(scheduler.run_frame());
+        }
+
+        let frames = frames.borrow();
+        let updates: Vec<u32> = frames.iter().map(|(updates, _)| *updates).collect();
+        assert_eq!(updates, [2, 1, 0, 3]);
+        assert_close(frames[0].1, 0.4);
+        assert_close(frames[1].1, 0.);
+        assert_close(frames[2].1, 0.4);
+        assert_close(frames[3].1, 0.8);
+    }
 }
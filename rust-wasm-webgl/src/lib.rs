@@ -1,12 +1,23 @@
 #![allow(dead_code)]
 
 use wasm_bindgen::prelude::*;
+mod clock;
 mod console;
 mod error;
 mod gl;
+mod input;
 mod js_loop;
+mod png;
+mod spatial;
+mod stats;
+mod text;
+mod trace;
+use clock::BrowserScheduler;
+use error::Error;
 use gl::*;
+use input::*;
 use js_loop::*;
+use stats::*;
 
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
@@ -16,7 +27,7 @@ pub fn start() {
     error::report(setup());
 }
 
-fn setup() -> Result<(), String> {
+fn setup() -> Result<(), Error> {
     let gl = Gl::init()?;
 
     let vertices = [
@@ -24,11 +35,60 @@ fn setup() -> Result<(), String> {
     ];
 
     let mut mesh = gl.create_mesh(&vertices)?;
+    let mut input = Input::new(
+        gl.canvas_element()
+            .ok_or_else(|| Error::Dom(String::from("Expected a canvas element")))?,
+    )?;
+    let mut camera = Camera2D::new();
+    let mut stats = FrameStats::new(120);
+    let mut hud = gl.create_hud()?;
+
+    let search = web_sys::window()
+        .ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?
+        .location()
+        .search()
+        .map_err(Error::js("Couldn't read the url"))?;
+    // `?trace` records the spans logged with F3
+    trace::set_enabled(search.contains("trace"));
+    // `?record` downloads the frames of the animation on a fixed time step
+    let recording = search.contains("record");
+    let mut recorder = Recorder::new(RecordOptions {
+        every: 2,
+        frames: Some(60),
+        ..RecordOptions::default()
+    });
+    hud.set_visible(!recording);
+
+    let clock = recorder.clock();
+    let draw_loop = move |gl: &mut Gl, time: FrameTime| {
+        input.update();
+        if input.key_pressed("F2") {
+            hud.toggle();
+        }
+        if input.key_pressed("F3") {
+            console::log!("{}", trace::chrome_trace());
+        }
+        let capture = input.key_pressed("F4");
+
+        let viewport = gl.canvas_size();
+        if input.button_down(MouseButton::Left) {
+            camera.pan(input.pointer_delta(), viewport);
+        }
+        let gesture = input.gesture();
+        camera.pan(gesture.pan, viewport);
+        camera.zoom_at(gesture.center, gesture.pinch, viewport);
+        camera.zoom_at(
+            input.pointer_position(),
+            (-input.wheel_delta()[1] / 500.).exp(),
+            viewport,
+        );
+        gl.set_camera_2d(&camera);
 
-    create_draw_loop(move |time| {
         gl.clear_canvas(&[0., 0., 0., 1.]);
 
-        let start = get_current_time()?;
+        stats.begin_frame()?;
+        stats.phase("setup")?;
+        let setup_span = trace::span!("setup");
 
         let margin = 0.95;
 
@@ -36,6 +96,7 @@ fn setup() -> Result<(), String> {
         let height = 200;
         let half_width = (width as f32 / 2. - 0.5) / margin;
         let half_height = (height as f32 / 2. - 0.5) / margin;
+        let seconds = time.seconds as f32;
 
         for x in 0..width {
             for y in 0..height {
@@ -46,10 +107,10 @@ fn setup() -> Result<(), String> {
                         0.5,
                     ]).
                     color(&[
-                        (5. * time.seconds + x as f32 / 8. + y as f32 / 9. + 1.).sin(),
+                        (5. * seconds + x as f32 / 8. + y as f32 / 9. + 1.).sin(),
                         // 0.,
-                        (10. * time.seconds - x as f32 / 10. + y as f32 / 10.).sin(),
-                        -(-6. * time.seconds + x as f32 / 10. + y as f32 / 4.).sin(),
+                        (10. * seconds - x as f32 / 10. + y as f32 / 10.).sin(),
+                        -(-6. * seconds + x as f32 / 10. + y as f32 / 4.).sin(),
                         // 1.,
                         1.,
                     ])
@@ -58,20 +119,45 @@ fn setup() -> Result<(), String> {
             }
         }
 
-        let after_draw = get_current_time()?;
-
+        drop(setup_span);
+        stats.phase("draw")?;
+        let draw_span = trace::span!("draw");
         mesh.draw();
+        drop(draw_span);
+        stats.end_frame()?;
+        hud.draw(gl, &stats)?;
+        gl.present()?;
+
+        if recording {
+            if let Some((index, png)) = recorder.end_frame(gl)? {
+                download(&png, &format!("frame-{:04}.png", index), "image/png")?;
+            }
+        } else if capture {
+            download(&gl.capture()?.to_png()?, "capture.png", "image/png")?;
+        }
 
         if time.frame_count % 60 == 0 {
+            let summary = stats.summary();
             console::log!(
-                "render: {}ms      setup: {}ms      draw: {}ms",
-                time.render_average * 1000.,
-                (after_draw - start) * 1000.,
-                (get_current_time()? - after_draw) * 1000.,
+                "frame p50: {}ms  p99: {}ms  janks: {}  {}",
+                summary.delta.p50 * 1000.,
+                summary.delta.p99 * 1000.,
+                summary.janks,
+                summary
+                    .phases
+                    .iter()
+                    .map(|(name, phase)| format!("{}: {}ms", name, phase.mean * 1000.))
+                    .collect::<Vec<_>>()
+                    .join("  "),
             );
         }
         Ok(())
-    })?;
+    };
 
+    if recording {
+        create_canvas_loop_with(clock, BrowserScheduler::new(), gl, draw_loop)?;
+    } else {
+        create_canvas_loop(gl, draw_loop)?;
+    }
     Ok(())
 }
@@ -0,0 +1,372 @@
+use crate::error::Error;
+
+/// Encodes 8 bit RGBA pixels, rows from top to bottom, as a PNG
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, Error> {
+    let row_len = width as usize * 4;
+    if rgba.len() != row_len * height as usize {
+        return Err(Error::Invalid(format!(
+            "Expected {} bytes for {}x{} RGBA pixels but found {}",
+            row_len * height as usize,
+            width,
+            height,
+            rgba.len()
+        )));
+    }
+
+    // Each row starts with its filter, `Sub` stores the difference with the pixel to the left
+    let mut filtered = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in rgba.chunks_exact(row_len.max(1)).take(height as usize) {
+        filtered.push(1);
+        filtered.extend(
+            (0..row.len()).map(|i| row[i].wrapping_sub(if i >= 4 { row[i - 4] } else { 0 })),
+        );
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, deflate, no interlacing
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib(&filtered));
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut table = [0u32; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let mut c = n as u32;
+        for _ in 0..8 {
+            c = if c & 1 != 0 {
+                0xedb88320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+        }
+        *entry = c;
+    }
+    !data.iter().fold(!0u32, |crc, &byte| {
+        table[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+
+/// Zlib stream of a single deflate block with the fixed Huffman codes,
+/// matches are found through the last position of each 3 byte hash
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    // Final block, fixed codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+
+    let mut last_positions = vec![usize::MAX; 1 << HASH_BITS];
+    let hash = |i: usize| {
+        let value = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], 0]);
+        (value.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize
+    };
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut match_len = 0;
+        let mut distance = 0;
+        if i + MIN_MATCH <= data.len() {
+            let h = hash(i);
+            let candidate = last_positions[h];
+            last_positions[h] = i;
+            if candidate != usize::MAX && i - candidate <= WINDOW {
+                let max_len = MAX_MATCH.min(data.len() - i);
+                while match_len < max_len && data[candidate + match_len] == data[i + match_len] {
+                    match_len += 1;
+                }
+                distance = i - candidate;
+            }
+        }
+
+        if match_len >= MIN_MATCH {
+            bits.write_length(match_len);
+            bits.write_distance(distance);
+            for j in i + 1..(i + match_len).min(data.len().saturating_sub(MIN_MATCH - 1)) {
+                last_positions[hash(j)] = j;
+            }
+            i += match_len;
+        } else {
+            bits.write_literal(data[i] as u16);
+            i += 1;
+        }
+    }
+    bits.write_literal(256);
+
+    let mut zlib = vec![0x78, 0x01];
+    zlib.extend(bits.finish());
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Least significant bit first
+    fn write(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Huffman codes are written from their most significant bit
+    fn write_code(&mut self, code: u32, len: u32) {
+        self.write(code.reverse_bits() >> (32 - len), len);
+    }
+
+    fn write_literal(&mut self, value: u16) {
+        let value = value as u32;
+        match value {
+            0..=143 => self.write_code(0b00110000 + value, 8),
+            144..=255 => self.write_code(0b110010000 + value - 144, 9),
+            256..=279 => self.write_code(value - 256, 7),
+            _ => self.write_code(0b11000000 + value - 280, 8),
+        }
+    }
+
+    fn write_length(&mut self, len: usize) {
+        let index = LENGTH_BASES
+            .iter()
+            .rposition(|&base| base as usize <= len)
+            .unwrap_or(0);
+        self.write_literal(257 + index as u16);
+        self.write(
+            (len - LENGTH_BASES[index] as usize) as u32,
+            LENGTH_EXTRA_BITS[index] as u32,
+        );
+    }
+
+    fn write_distance(&mut self, distance: usize) {
+        let index = DISTANCE_BASES
+            .iter()
+            .rposition(|&base| base as usize <= distance)
+            .unwrap_or(0);
+        self.write_code(index as u32, 5);
+        self.write(
+            (distance - DISTANCE_BASES[index] as usize) as u32,
+            DISTANCE_EXTRA_BITS[index] as u32,
+        );
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_reference_values() {
+        assert_eq!(crc32(b"IEND"), 0xae426082);
+        assert_eq!(crc32(b"123456789"), 0xcbf43926);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        // Long enough for the sums to be reduced several times
+        assert_eq!(adler32(&[0xff; 100_000]), 0x149a302c);
+    }
+
+    /// Zlib stream of stored blocks, the data as is
+    fn stored_zlib(data: &[u8]) -> Vec<u8> {
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = data.chunks(0xffff).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            zlib.push((i + 1 == blocks.len()) as u8);
+            zlib.extend_from_slice(&(block.len() as u16).to_le_bytes());
+            zlib.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        if blocks.is_empty() {
+            zlib.extend_from_slice(&[1, 0, 0, 0xff, 0xff]);
+        }
+        zlib.extend_from_slice(&adler32(data).to_be_bytes());
+        zlib
+    }
+
+    struct BitReader<'a> {
+        data: &'a [u8],
+        bit: usize,
+    }
+
+    impl BitReader<'_> {
+        fn read(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |value, i| {
+                let bit = (self.data[self.bit / 8] >> (self.bit % 8)) & 1;
+                self.bit += 1;
+                value | (bit as u32) << i
+            })
+        }
+
+        /// Huffman codes start from their most significant bit
+        fn read_code(&mut self, len: u32) -> u32 {
+            (0..len).fold(0, |code, _| code << 1 | self.read(1))
+        }
+
+        fn read_literal(&mut self) -> u32 {
+            let code = self.read_code(7);
+            if code <= 0b0010111 {
+                return code + 256;
+            }
+            let code = code << 1 | self.read(1);
+            match code {
+                0b00110000..=0b10111111 => code - 0b00110000,
+                0b11000000..=0b11000111 => code - 0b11000000 + 280,
+                _ => (code << 1 | self.read(1)) - 0b110010000 + 144,
+            }
+        }
+    }
+
+    /// Inflates stored and fixed Huffman blocks, checking the Adler-32
+    fn inflate(zlib: &[u8]) -> Vec<u8> {
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0);
+        let mut bits = BitReader {
+            data: &zlib[2..],
+            bit: 0,
+        };
+        let mut data = vec![];
+        loop {
+            let last = bits.read(1) == 1;
+            match bits.read(2) {
+                0 => {
+                    bits.bit = bits.bit.div_ceil(8) * 8;
+                    let len = bits.read(16) as usize;
+                    assert_eq!(bits.read(16) as usize, !len & 0xffff);
+                    let start = bits.bit / 8;
+                    data.extend_from_slice(&bits.data[start..start + len]);
+                    bits.bit += len * 8;
+                }
+                1 => loop {
+                    let literal = bits.read_literal();
+                    match literal {
+                        0..=255 => data.push(literal as u8),
+                        256 => break,
+                        _ => {
+                            let index = literal as usize - 257;
+                            let len = LENGTH_BASES[index] as usize
+                                + bits.read(LENGTH_EXTRA_BITS[index] as u32) as usize;
+                            let index = bits.read_code(5) as usize;
+                            let distance = DISTANCE_BASES[index] as usize
+                                + bits.read(DISTANCE_EXTRA_BITS[index] as u32) as usize;
+                            for _ in 0..len {
+                                data.push(data[data.len() - distance]);
+                            }
+                        }
+                    }
+                },
+                kind => panic!("Unexpected block type {}", kind),
+            }
+            if last {
+                break;
+            }
+        }
+        let end = 2 + bits.bit.div_ceil(8);
+        assert_eq!(&zlib[end..], &adler32(&data).to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn fixed_blocks_inflate_like_stored_ones() {
+        let pattern: Vec<u8> = (0..70_000u32).map(|i| (i % 251) as u8).collect();
+        let noise: Vec<u8> = (0..5_000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect();
+        for data in [vec![], vec![7], vec![0; 1000], pattern, noise] {
+            assert_eq!(inflate(&stored_zlib(&data)), data);
+            assert_eq!(inflate(&zlib(&data)), data);
+        }
+    }
+
+    #[test]
+    fn png_chunks_hold_the_filtered_pixels() {
+        let rgba: Vec<u8> = (0..3 * 2 * 4).map(|i| (i * 37) as u8).collect();
+        let png = encode_png(3, 2, &rgba).unwrap();
+        assert_eq!(png[..8], [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]);
+
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind_and_data, crc) = rest[4..8 + len + 4].split_at(4 + len);
+            assert_eq!(crc, crc32(kind_and_data).to_be_bytes());
+            chunks.push((&kind_and_data[..4], &kind_and_data[4..]));
+            rest = &rest[8 + len + 4..];
+        }
+        let kinds: Vec<&[u8]> = chunks.iter().map(|(kind, _)| *kind).collect();
+        assert_eq!(kinds, [b"IHDR", b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 3, 0, 0, 0, 2, 8, 6, 0, 0, 0]);
+
+        let filtered = inflate(chunks[1].1);
+        let mut pixels = vec![];
+        for row in filtered.chunks_exact(1 + 3 * 4) {
+            assert_eq!(row[0], 1);
+            let start = pixels.len();
+            for (i, &byte) in row[1..].iter().enumerate() {
+                let left = if i >= 4 { pixels[start + i - 4] } else { 0 };
+                pixels.push(byte.wrapping_add(left));
+            }
+        }
+        assert_eq!(pixels, rgba);
+        assert!(encode_png(3, 2, &rgba[1..]).is_err());
+    }
+}
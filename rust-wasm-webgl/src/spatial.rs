@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+/// Items covering more cells, or with non-finite bounds, are kept out of the
+/// cells and checked by every query
+const MAX_ITEM_CELLS: i64 = 1024;
+const MAX_CELL: f32 = (1 << 29) as f32;
+
+/// Axis aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl Aabb {
+    pub fn new(min: [f32; 2], max: [f32; 2]) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_center(center: [f32; 2], half_size: [f32; 2]) -> Self {
+        Self {
+            min: [center[0] - half_size[0], center[1] - half_size[1]],
+            max: [center[0] + half_size[0], center[1] + half_size[1]],
+        }
+    }
+
+    /// Bounds of `[x, y, x, y, ...]` coordinates, like the vertices of a 2D mesh
+    pub fn of_points(coordinates: &[f32]) -> Option<Self> {
+        let mut points = coordinates.chunks_exact(2);
+        let first = points.next()?;
+        let mut bounds = Self::new([first[0], first[1]], [first[0], first[1]]);
+        for point in points {
+            bounds.min = [bounds.min[0].min(point[0]), bounds.min[1].min(point[1])];
+            bounds.max = [bounds.max[0].max(point[0]), bounds.max[1].max(point[1])];
+        }
+        Some(bounds)
+    }
+
+    pub fn center(&self) -> [f32; 2] {
+        [
+            (self.min[0] + self.max[0]) / 2.,
+            (self.min[1] + self.max[1]) / 2.,
+        ]
+    }
+
+    pub fn half_size(&self) -> [f32; 2] {
+        [
+            (self.max[0] - self.min[0]) / 2.,
+            (self.max[1] - self.min[1]) / 2.,
+        ]
+    }
+
+    pub fn contains(&self, point: [f32; 2]) -> bool {
+        point[0] >= self.min[0]
+            && point[0] <= self.max[0]
+            && point[1] >= self.min[1]
+            && point[1] <= self.max[1]
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min[0] <= other.max[0]
+            && self.max[0] >= other.min[0]
+            && self.min[1] <= other.max[1]
+            && self.max[1] >= other.min[1]
+    }
+
+    /// 0 when the point is inside, NaN when the bounds have NaN coordinates
+    pub fn distance_squared(&self, point: [f32; 2]) -> f32 {
+        if self.min.iter().chain(&self.max).any(|c| c.is_nan()) {
+            return f32::NAN;
+        }
+        let dx = (self.min[0] - point[0]).max(point[0] - self.max[0]).max(0.);
+        let dy = (self.min[1] - point[1]).max(point[1] - self.max[1]).max(0.);
+        dx * dx + dy * dy
+    }
+}
+
+/// Uniform grid over the bounds of items, for point, rectangle, nearest and
+/// overlap queries. Either rebuilt every frame with `clear` and `insert` or
+/// kept and updated with `set_bounds`.
+///
+/// Works best when `cell_size` is about the size of the typical item.
+pub struct SpatialGrid<T> {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// Items too large for the cells, see `MAX_ITEM_CELLS`
+    large: Vec<usize>,
+    items: Vec<(Aabb, T)>,
+}
+
+impl<T> SpatialGrid<T> {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            large: vec![],
+            items: vec![],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.large.clear();
+        self.items.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the index of the item, valid until the next `clear`
+    pub fn insert(&mut self, bounds: Aabb, item: T) -> usize {
+        let index = self.items.len();
+        self.items.push((bounds, item));
+        self.add_to_cells(index, &bounds);
+        index
+    }
+
+    pub fn get(&self, index: usize) -> Option<(&Aabb, &T)> {
+        self.items.get(index).map(|(bounds, item)| (bounds, item))
+    }
+
+    /// Moves an item without rebuilding the grid. Returns its previous bounds,
+    /// or `None` when there is no item at `index`.
+    pub fn set_bounds(&mut self, index: usize, bounds: Aabb) -> Option<Aabb> {
+        let old = self.items.get(index)?.0;
+        match self.cell_range(&old) {
+            Some((min, max)) => {
+                for x in min.0..=max.0 {
+                    for y in min.1..=max.1 {
+                        if let Some(cell) = self.cells.get_mut(&(x, y)) {
+                            cell.retain(|&i| i != index);
+                        }
+                    }
+                }
+            }
+            None => self.large.retain(|&i| i != index),
+        }
+        self.items[index].0 = bounds;
+        self.add_to_cells(index, &bounds);
+        Some(old)
+    }
+
+    pub fn query_point(&self, point: [f32; 2]) -> Vec<(usize, &T)> {
+        self.cells
+            .get(&self.cell(point))
+            .into_iter()
+            .flatten()
+            .chain(&self.large)
+            .filter(|&&index| self.items[index].0.contains(point))
+            .map(|&index| (index, &self.items[index].1))
+            .collect()
+    }
+
+    pub fn query_rect(&self, rect: &Aabb) -> Vec<(usize, &T)> {
+        let mut indices = self.candidates(rect);
+        indices.extend(&self.large);
+        indices.retain(|&index| self.items[index].0.intersects(rect));
+        indices
+            .into_iter()
+            .map(|index| (index, &self.items[index].1))
+            .collect()
+    }
+
+    /// Closest item to `point` within `max_distance`, measured to the item bounds
+    pub fn nearest(&self, point: [f32; 2], max_distance: f32) -> Option<(usize, &T)> {
+        let mut best: Option<(usize, f32)> = None;
+        let consider = |best: &mut Option<(usize, f32)>, index: usize| {
+            let distance = self.items[index].0.distance_squared(point);
+            if distance <= max_distance * max_distance
+                && best.is_none_or(|(_, best)| distance < best)
+            {
+                *best = Some((index, distance));
+            }
+        };
+        for &index in &self.large {
+            consider(&mut best, index);
+        }
+
+        if let Some((occupied_min, occupied_max)) = self.occupied_cells() {
+            let center = self.cell(point);
+            let ring_to = |min: i32, max: i32, at: i32| (min - at).max(at - max).max(0);
+            // Rings closer than the occupied cells are empty
+            let first_ring = ring_to(occupied_min.0, occupied_max.0, center.0).max(ring_to(
+                occupied_min.1,
+                occupied_max.1,
+                center.1,
+            ));
+            let last_ring = [
+                center.0 - occupied_min.0,
+                occupied_max.0 - center.0,
+                center.1 - occupied_min.1,
+                occupied_max.1 - center.1,
+            ]
+            .into_iter()
+            .max()
+            .unwrap_or(0)
+            .max(0);
+
+            for ring in first_ring..=last_ring {
+                // Cells of this ring and beyond are at least this far from the point
+                let ring_distance = (ring - 1).max(0) as f32 * self.cell_size;
+                if ring_distance > max_distance
+                    || best.is_some_and(|(_, distance)| distance <= ring_distance * ring_distance)
+                {
+                    break;
+                }
+
+                // Past the size of the grid, going through its cells is faster than the rings
+                if 8 * ring as usize > self.cells.len() {
+                    for (&(x, y), indices) in &self.cells {
+                        if (x - center.0).abs().max((y - center.1).abs()) >= ring {
+                            for &index in indices {
+                                consider(&mut best, index);
+                            }
+                        }
+                    }
+                    break;
+                }
+
+                for (x, y) in ring_cells(center, ring) {
+                    for &index in self.cells.get(&(x, y)).into_iter().flatten() {
+                        consider(&mut best, index);
+                    }
+                }
+            }
+        }
+        best.map(|(index, _)| (index, &self.items[index].1))
+    }
+
+    /// Every pair of items whose bounds intersect, each reported once
+    pub fn overlapping_pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for &a in &self.large {
+            for (b, (bounds_b, _)) in self.items.iter().enumerate() {
+                let b_is_large = self.large.contains(&b);
+                if b != a && (!b_is_large || b > a) && self.items[a].0.intersects(bounds_b) {
+                    pairs.push((a.min(b), a.max(b)));
+                }
+            }
+        }
+        for (&cell, indices) in &self.cells {
+            for (i, &a) in indices.iter().enumerate() {
+                for &b in &indices[i + 1..] {
+                    let (bounds_a, bounds_b) = (&self.items[a].0, &self.items[b].0);
+                    if !bounds_a.intersects(bounds_b) {
+                        continue;
+                    }
+                    // Only the cell holding the corner of the intersection reports the pair
+                    let corner = [
+                        bounds_a.min[0].max(bounds_b.min[0]),
+                        bounds_a.min[1].max(bounds_b.min[1]),
+                    ];
+                    if self.cell(corner) == cell {
+                        pairs.push((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+        pairs
+    }
+
+    fn add_to_cells(&mut self, index: usize, bounds: &Aabb) {
+        match self.cell_range(bounds) {
+            Some((min, max)) => {
+                for x in min.0..=max.0 {
+                    for y in min.1..=max.1 {
+                        self.cells.entry((x, y)).or_default().push(index);
+                    }
+                }
+            }
+            None => self.large.push(index),
+        }
+    }
+
+    /// First and last cells of `bounds`, `None` past `MAX_ITEM_CELLS` or for non-finite bounds
+    fn cell_range(&self, bounds: &Aabb) -> Option<((i32, i32), (i32, i32))> {
+        if !bounds.min.iter().chain(&bounds.max).all(|c| c.is_finite()) {
+            return None;
+        }
+        let (min, max) = (self.cell(bounds.min), self.cell(bounds.max));
+        let cells_count = cells_count(min, max);
+        (cells_count <= MAX_ITEM_CELLS).then_some((min, max))
+    }
+
+    fn candidates(&self, rect: &Aabb) -> Vec<usize> {
+        let (min, max) = (self.cell(rect.min), self.cell(rect.max));
+        let cells_count = cells_count(min, max);
+        let mut indices = vec![];
+        if cells_count > self.cells.len() as i64 {
+            // Larger than the grid, going through its cells is faster
+            for (&(x, y), cell) in &self.cells {
+                if (min.0..=max.0).contains(&x) && (min.1..=max.1).contains(&y) {
+                    indices.extend(cell);
+                }
+            }
+        } else {
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    indices.extend(self.cells.get(&(x, y)).into_iter().flatten());
+                }
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    fn occupied_cells(&self) -> Option<((i32, i32), (i32, i32))> {
+        self.cells
+            .iter()
+            .filter(|(_, indices)| !indices.is_empty())
+            .map(|(&cell, _)| cell)
+            .fold(None, |bounds, (x, y)| match bounds {
+                None => Some(((x, y), (x, y))),
+                Some((min, max)) => {
+                    Some(((min.0.min(x), min.1.min(y)), (max.0.max(x), max.1.max(y))))
+                }
+            })
+    }
+
+    /// Clamped so that differences of cells don't overflow
+    fn cell(&self, point: [f32; 2]) -> (i32, i32) {
+        let cell = |c: f32| (c / self.cell_size).floor().clamp(-MAX_CELL, MAX_CELL) as i32;
+        (cell(point[0]), cell(point[1]))
+    }
+}
+
+fn cells_count(min: (i32, i32), max: (i32, i32)) -> i64 {
+    (max.0 as i64 - min.0 as i64 + 1).max(0) * (max.1 as i64 - min.1 as i64 + 1).max(0)
+}
+
+/// Cells at exactly `ring` cells (Chebyshev distance) from `center`
+fn ring_cells(center: (i32, i32), ring: i32) -> impl Iterator<Item = (i32, i32)> {
+    (-ring..=ring).flat_map(move |dx| {
+        (-ring..=ring)
+            .filter(move |&dy| dx.abs() == ring || dy.abs() == ring)
+            .map(move |dy| (center.0 + dx, center.1 + dy))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indices<T>(items: Vec<(usize, &T)>) -> Vec<usize> {
+        let mut indices: Vec<usize> = items.into_iter().map(|(index, _)| index).collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    #[test]
+    fn queries_find_the_items_under_them() {
+        let mut grid = SpatialGrid::new(10.);
+        let a = grid.insert(Aabb::new([0., 0.], [5., 5.]), 'a');
+        let b = grid.insert(Aabb::new([4., 4.], [25., 8.]), 'b');
+        grid.insert(Aabb::new([-30., -30.], [-20., -20.]), 'c');
+
+        assert_eq!(indices(grid.query_point([4.5, 4.5])), [a, b]);
+        assert_eq!(indices(grid.query_point([20., 6.])), [b]);
+        assert!(grid.query_point([50., 50.]).is_empty());
+        assert_eq!(
+            indices(grid.query_rect(&Aabb::new([-1., -1.], [1., 1.]))),
+            [a]
+        );
+        assert_eq!(grid.overlapping_pairs(), [(a, b)]);
+    }
+
+    #[test]
+    fn nearest_is_measured_to_the_bounds() {
+        let mut grid = SpatialGrid::new(1.);
+        let near = grid.insert(Aabb::new([3., 0.], [4., 1.]), ());
+        grid.insert(Aabb::from_center([10., 0.], [0.5, 0.5]), ());
+
+        assert_eq!(grid.nearest([0., 0.], 5.).map(|(i, _)| i), Some(near));
+        assert_eq!(grid.nearest([0., 0.], 2.), None);
+        assert_eq!(
+            grid.nearest([1e6, 0.], f32::INFINITY).map(|(i, _)| i),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn set_bounds_moves_the_item() {
+        let mut grid = SpatialGrid::new(1.);
+        let index = grid.insert(Aabb::new([0., 0.], [1., 1.]), ());
+
+        let moved = Aabb::new([5., 5.], [6., 6.]);
+        assert_eq!(
+            grid.set_bounds(index, moved),
+            Some(Aabb::new([0., 0.], [1., 1.]))
+        );
+        assert!(grid.query_point([0.5, 0.5]).is_empty());
+        assert_eq!(indices(grid.query_point([5.5, 5.5])), [index]);
+        assert_eq!(grid.set_bounds(7, moved), None);
+    }
+
+    #[test]
+    fn unbounded_items_are_kept_out_of_the_cells() {
+        let mut grid = SpatialGrid::new(1.);
+        let small = grid.insert(Aabb::new([0., 0.], [1., 1.]), ());
+        let infinite = grid.insert(Aabb::new([f32::NEG_INFINITY, 0.], [f32::INFINITY, 0.5]), ());
+        let huge = grid.insert(Aabb::new([-1e9, -1e9], [1e9, 1e9]), ());
+        let nan = grid.insert(Aabb::new([f32::NAN, 0.], [1., 1.]), ());
+
+        assert_eq!(
+            indices(grid.query_point([0.5, 0.25])),
+            [small, infinite, huge]
+        );
+        assert_eq!(
+            indices(grid.query_rect(&Aabb::new([-1e30, -1e30], [1e30, 1e30]))),
+            [small, infinite, huge]
+        );
+        assert_eq!(grid.overlapping_pairs().len(), 3);
+
+        grid.set_bounds(huge, Aabb::new([3., 3.], [4., 4.]));
+        grid.set_bounds(nan, Aabb::new([f32::NAN; 2], [f32::NAN; 2]));
+        assert_eq!(indices(grid.query_point([3.5, 3.5])), [huge]);
+        assert_eq!(grid.nearest([0.5, 0.75], 0.).map(|(i, _)| i), Some(small));
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::VecDeque;
+
+use crate::{
+    clock::{BrowserClock, Clock},
+    error::Error,
+};
+
+/// Frames taking longer than this many budgets missed at least one refresh
+const JANK_FACTOR: f32 = 1.5;
+
+/// Timings of one frame, in seconds
+#[derive(Debug, Clone)]
+pub struct FrameSample {
+    pub frame: u32,
+    /// Time since the previous frame began
+    pub delta: f32,
+    /// Time between `begin_frame` and `end_frame`
+    pub work: f32,
+    /// Named parts of `work`, in the order they ran
+    pub phases: Vec<(&'static str, f32)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Distribution {
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+}
+
+impl Distribution {
+    fn of(mut values: Vec<f32>) -> Self {
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(f32::total_cmp);
+        // Nearest rank
+        let percentile = |p: f32| values[((p * values.len() as f32).ceil() as usize).max(1) - 1];
+        Self {
+            mean: values.iter().sum::<f32>() / values.len() as f32,
+            min: values[0],
+            max: values[values.len() - 1],
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+        }
+    }
+}
+
+/// Statistics over the frames kept by `FrameStats`
+#[derive(Debug, Clone, Default)]
+pub struct FrameSummary {
+    pub frames: usize,
+    pub delta: Distribution,
+    pub work: Distribution,
+    /// Over the frames that ran each phase
+    pub phases: Vec<(&'static str, Distribution)>,
+    /// Frames whose delta missed a refresh
+    pub janks: usize,
+    /// Frames whose work alone took longer than the budget
+    pub over_budget: usize,
+}
+
+/// Records the timings of the last frames, split into named phases.
+///
+/// ```ignore
+/// stats.begin_frame()?;
+/// stats.phase("setup")?;
+/// // ...
+/// stats.phase("draw")?;
+/// // ...
+/// stats.end_frame()?;
+/// ```
+pub struct FrameStats {
+    clock: Box<dyn Clock>,
+    window: usize,
+    budget: f32,
+    samples: VecDeque<FrameSample>,
+    frame_count: u32,
+    /// Times of the clock
+    frame_start: Option<f64>,
+    previous_start: Option<f64>,
+    phases: Vec<(&'static str, f32)>,
+    phase_start: Option<(&'static str, f64)>,
+}
+
+impl FrameStats {
+    /// Keeps the last `window` frames
+    pub fn new(window: usize) -> Self {
+        Self::with_clock(window, BrowserClock)
+    }
+
+    pub fn with_clock(window: usize, clock: impl Clock + 'static) -> Self {
+        Self {
+            clock: Box::new(clock),
+            window: window.max(1),
+            budget: 1. / 60.,
+            samples: VecDeque::new(),
+            frame_count: 0,
+            frame_start: None,
+            previous_start: None,
+            phases: vec![],
+            phase_start: None,
+        }
+    }
+
+    /// Seconds a frame may take, 1/60 by default
+    pub fn set_budget(&mut self, budget: f32) {
+        self.budget = budget;
+    }
+
+    pub fn budget(&self) -> f32 {
+        self.budget
+    }
+
+    pub fn set_window(&mut self, window: usize) {
+        self.window = window.max(1);
+        while self.samples.len() > self.window {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn begin_frame(&mut self) -> Result<(), Error> {
+        self.frame_start = Some(self.clock.now()?);
+        self.phases.clear();
+        self.phase_start = None;
+        Ok(())
+    }
+
+    /// Ends the current phase and starts `name`
+    pub fn phase(&mut self, name: &'static str) -> Result<(), Error> {
+        let now = self.clock.now()?;
+        self.close_phase(now);
+        self.phase_start = Some((name, now));
+        Ok(())
+    }
+
+    pub fn end_frame(&mut self) -> Result<(), Error> {
+        let now = self.clock.now()?;
+        let start = self
+            .frame_start
+            .take()
+            .ok_or_else(|| Error::Invalid(String::from("end_frame called without begin_frame")))?;
+        self.close_phase(now);
+
+        self.frame_count += 1;
+        let delta = self
+            .previous_start
+            .map_or(self.budget, |previous| (start - previous) as f32);
+        self.previous_start = Some(start);
+
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(FrameSample {
+            frame: self.frame_count,
+            delta,
+            work: (now - start) as f32,
+            phases: std::mem::take(&mut self.phases),
+        });
+        Ok(())
+    }
+
+    pub fn last(&self) -> Option<&FrameSample> {
+        self.samples.back()
+    }
+
+    /// Oldest first
+    pub fn samples(&self) -> impl Iterator<Item = &FrameSample> {
+        self.samples.iter()
+    }
+
+    pub fn is_jank(&self, sample: &FrameSample) -> bool {
+        sample.delta > self.budget * JANK_FACTOR
+    }
+
+    pub fn summary(&self) -> FrameSummary {
+        let mut phase_names: Vec<&'static str> = vec![];
+        for sample in &self.samples {
+            for (name, _) in &sample.phases {
+                if !phase_names.contains(name) {
+                    phase_names.push(name);
+                }
+            }
+        }
+
+        FrameSummary {
+            frames: self.samples.len(),
+            delta: Distribution::of(self.samples.iter().map(|s| s.delta).collect()),
+            work: Distribution::of(self.samples.iter().map(|s| s.work).collect()),
+            phases: phase_names
+                .into_iter()
+                .map(|name| {
+                    let durations = self
+                        .samples
+                        .iter()
+                        .flat_map(|s| &s.phases)
+                        .filter(|(phase, _)| *phase == name)
+                        .map(|(_, duration)| *duration)
+                        .collect();
+                    (name, Distribution::of(durations))
+                })
+                .collect(),
+            janks: self.samples.iter().filter(|s| self.is_jank(s)).count(),
+            over_budget: self.samples.iter().filter(|s| s.work > self.budget).count(),
+        }
+    }
+
+    /// The kept frames as `{"budget": s, "frames": [{"frame", "delta", "work", "phases": {name: s}}]}`,
+    /// with `null` for the times that aren't finite
+    pub fn to_json(&self) -> String {
+        let frames: Vec<String> = self
+            .samples
+            .iter()
+            .map(|sample| {
+                let phases: Vec<String> = sample
+                    .phases
+                    .iter()
+                    .map(|(name, duration)| {
+                        format!("{}:{}", json_string(name), json_number(*duration))
+                    })
+                    .collect();
+                format!(
+                    r#"{{"frame":{},"delta":{},"work":{},"phases":{{{}}}}}"#,
+                    sample.frame,
+                    json_number(sample.delta),
+                    json_number(sample.work),
+                    phases.join(",")
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"budget":{},"frames":[{}]}}"#,
+            json_number(self.budget),
+            frames.join(",")
+        )
+    }
+
+    fn close_phase(&mut self, now: f64) {
+        if let Some((name, start)) = self.phase_start.take() {
+            let elapsed = (now - start) as f32;
+            match self.phases.iter_mut().find(|(phase, _)| *phase == name) {
+                Some((_, duration)) => *duration += elapsed,
+                None => self.phases.push((name, elapsed)),
+            }
+        }
+    }
+}
+
+/// JSON has no NaN or infinities
+pub(crate) fn json_number(value: f32) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        String::from("null")
+    }
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut json = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    /// Runs a frame of `work` seconds, the next one begins `delta` seconds after it began
+    fn frame(stats: &mut FrameStats, clock: &ManualClock, work: f64, delta: f64) {
+        stats.begin_frame().unwrap();
+        clock.advance(work);
+        stats.end_frame().unwrap();
+        clock.advance(delta - work);
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let distribution = Distribution::of((1..=100).rev().map(|v| v as f32).collect());
+        assert_eq!(distribution.min, 1.);
+        assert_eq!(distribution.max, 100.);
+        assert_eq!(distribution.mean, 50.5);
+        assert_eq!(distribution.p50, 50.);
+        assert_eq!(distribution.p95, 95.);
+        assert_eq!(distribution.p99, 99.);
+        assert_eq!(Distribution::of(vec![3.]).p99, 3.);
+        assert_eq!(Distribution::of(vec![]), Distribution::default());
+    }
+
+    #[test]
+    fn keeps_the_last_frames_of_the_window() {
+        let clock = ManualClock::new(0.);
+        let mut stats = FrameStats::with_clock(3, clock.clone());
+        for _ in 0..5 {
+            frame(&mut stats, &clock, 0.25, 0.5);
+        }
+        let frames: Vec<u32> = stats.samples().map(|sample| sample.frame).collect();
+        assert_eq!(frames, [3, 4, 5]);
+
+        stats.set_window(2);
+        let frames: Vec<u32> = stats.samples().map(|sample| sample.frame).collect();
+        assert_eq!(frames, [4, 5]);
+        assert_eq!(stats.summary().frames, 2);
+        assert!(stats.end_frame().is_err());
+    }
+
+    #[test]
+    fn counts_janks_and_frames_over_budget() {
+        let clock = ManualClock::new(0.);
+        let mut stats = FrameStats::with_clock(10, clock.clone());
+        stats.set_budget(0.5);
+        // The first delta is the budget, then 0.5, 1 (jank) and 0.5
+        frame(&mut stats, &clock, 0.25, 0.5);
+        frame(&mut stats, &clock, 0.75, 1.);
+        frame(&mut stats, &clock, 0.25, 0.5);
+        frame(&mut stats, &clock, 0.25, 0.5);
+
+        let summary = stats.summary();
+        assert_eq!(summary.janks, 1);
+        assert_eq!(summary.over_budget, 1);
+        assert_eq!(summary.delta.max, 1.);
+        assert_eq!(summary.work.max, 0.75);
+    }
+
+    #[test]
+    fn phases_add_up_by_name() {
+        let clock = ManualClock::new(0.);
+        let mut stats = FrameStats::with_clock(10, clock.clone());
+        stats.begin_frame().unwrap();
+        stats.phase("update").unwrap();
+        clock.advance(0.25);
+        stats.phase("draw").unwrap();
+        clock.advance(0.5);
+        stats.phase("update").unwrap();
+        clock.advance(0.25);
+        stats.end_frame().unwrap();
+
+        assert_eq!(
+            stats.last().unwrap().phases,
+            [("update", 0.5), ("draw", 0.5)]
+        );
+        let phases: Vec<&str> = stats
+            .summary()
+            .phases
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+        assert_eq!(phases, ["update", "draw"]);
+    }
+
+    #[test]
+    fn json_has_no_invalid_numbers() {
+        let clock = ManualClock::new(0.);
+        let mut stats = FrameStats::with_clock(10, clock.clone());
+        stats.set_budget(0.5);
+        stats.begin_frame().unwrap();
+        stats.phase("a\"b").unwrap();
+        clock.advance(0.25);
+        stats.end_frame().unwrap();
+        assert_eq!(
+            stats.to_json(),
+            r#"{"budget":0.5,"frames":[{"frame":1,"delta":0.5,"work":0.25,"phases":{"a\"b":0.25}}]}"#
+        );
+
+        stats.set_budget(f32::NAN);
+        frame(&mut stats, &clock, f64::INFINITY, 1.);
+        let json = stats.to_json();
+        assert!(json.starts_with(r#"{"budget":null,"#), "{}", json);
+        assert!(!json.contains("NaN") && !json.contains("inf"), "{}", json);
+    }
+}
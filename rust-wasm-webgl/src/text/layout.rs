@@ -0,0 +1,215 @@
+use super::ttf::Font;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutOptions {
+    /// Em size in output units
+    pub size: f32,
+    /// Multiplier of the font line height
+    pub line_height: f32,
+    pub align: Align,
+    /// Lines are wrapped at spaces (or inside words if needed) to fit this width
+    pub max_width: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PositionedGlyph {
+    pub glyph: u16,
+    /// Glyph origin on the baseline. The text block starts at (0, 0)
+    /// and grows to +x and -y.
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            size: 1.,
+            line_height: 1.,
+            align: Align::Left,
+            max_width: None,
+        }
+    }
+}
+
+struct LineGlyph {
+    glyph: u16,
+    x: f32,
+    advance: f32,
+    space: bool,
+}
+
+pub fn layout_text(font: &Font, text: &str, options: &LayoutOptions) -> TextLayout {
+    let scale = options.size / font.units_per_em();
+    let line_height = font.line_height() * scale * options.line_height;
+
+    let mut layout = TextLayout::default();
+    let mut lines: Vec<Vec<LineGlyph>> = vec![];
+
+    for paragraph in text.split('\n') {
+        let mut line: Vec<LineGlyph> = vec![];
+        let mut x = 0.;
+        let mut previous = None;
+        // Index of the first glyph after the last space of the line
+        let mut break_index = None;
+
+        for c in paragraph.chars() {
+            let glyph = font.glyph_index(c).unwrap_or(0);
+            let space = c.is_whitespace();
+            if let Some(previous) = previous {
+                x += font.kerning(previous, glyph) * scale;
+            }
+            let advance = font.advance(glyph) * scale;
+
+            if let Some(max_width) = options.max_width {
+                if !space && !line.is_empty() && x + advance > max_width {
+                    let rest = match break_index {
+                        Some(index) => line.split_off(index),
+                        None => vec![],
+                    };
+                    lines.push(line);
+
+                    let offset = rest.first().map_or(x, |g| g.x);
+                    line = rest;
+                    for g in &mut line {
+                        g.x -= offset;
+                    }
+                    x -= offset;
+                    break_index = None;
+                }
+            }
+
+            line.push(LineGlyph {
+                glyph,
+                x,
+                advance,
+                space,
+            });
+            x += advance;
+            if space {
+                break_index = Some(line.len());
+            }
+            previous = Some(glyph);
+        }
+        lines.push(line);
+    }
+
+    let widths: Vec<f32> = lines
+        .iter()
+        .map(|line| {
+            line.iter()
+                .rev()
+                .find(|g| !g.space)
+                .map_or(0., |g| g.x + g.advance)
+        })
+        .collect();
+    let block_width = options
+        .max_width
+        .unwrap_or_else(|| widths.iter().cloned().fold(0., f32::max));
+
+    for (i, line) in lines.iter().enumerate() {
+        let offset = match options.align {
+            Align::Left => 0.,
+            Align::Center => (block_width - widths[i]) / 2.,
+            Align::Right => block_width - widths[i],
+        };
+        let baseline = -font.ascender() * scale - i as f32 * line_height;
+        for g in line.iter().filter(|g| !g.space) {
+            layout.glyphs.push(PositionedGlyph {
+                glyph: g.glyph,
+                x: g.x + offset,
+                y: baseline,
+            });
+        }
+    }
+
+    layout.width = widths.iter().cloned().fold(0., f32::max);
+    layout.height = lines.len() as f32 * line_height;
+    layout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::test_font::{self, *};
+
+    fn options(max_width: Option<f32>, align: Align) -> LayoutOptions {
+        LayoutOptions {
+            size: UNITS_PER_EM as f32,
+            max_width,
+            align,
+            ..LayoutOptions::default()
+        }
+    }
+
+    #[test]
+    fn places_glyphs_with_advances_and_kerning() {
+        let font = test_font::font();
+        let layout = layout_text(&font, "AB", &options(None, Align::Left));
+        let positions: Vec<_> = layout.glyphs.iter().map(|g| (g.glyph, g.x, g.y)).collect();
+        let b_x = ADVANCES[1] as f32 + KERNING as f32;
+        assert_eq!(
+            positions,
+            [(1, 0., -ASCENDER as f32), (2, b_x, -ASCENDER as f32)]
+        );
+        assert_eq!(layout.width, b_x + ADVANCES[2] as f32);
+        assert_eq!(layout.height, 1000.);
+    }
+
+    #[test]
+    fn scales_to_the_em_size() {
+        let font = test_font::font();
+        let layout = layout_text(
+            &font,
+            "A",
+            &LayoutOptions {
+                size: 10.,
+                line_height: 1.5,
+                ..LayoutOptions::default()
+            },
+        );
+        assert_eq!(layout.width, 6.);
+        assert_eq!(layout.height, 15.);
+        assert_eq!(layout.glyphs[0].y, -8.);
+    }
+
+    #[test]
+    fn wraps_at_spaces_and_skips_them() {
+        let font = test_font::font();
+        let layout = layout_text(&font, "A A", &options(Some(1000.), Align::Left));
+        let positions: Vec<_> = layout.glyphs.iter().map(|g| (g.x, g.y)).collect();
+        assert_eq!(positions, [(0., -800.), (0., -1800.)]);
+        assert_eq!(layout.height, 2000.);
+    }
+
+    #[test]
+    fn wraps_inside_words_without_spaces() {
+        let font = test_font::font();
+        let layout = layout_text(&font, "AAA", &options(Some(1300.), Align::Left));
+        let positions: Vec<_> = layout.glyphs.iter().map(|g| (g.x, g.y)).collect();
+        assert_eq!(positions, [(0., -800.), (600., -800.), (0., -1800.)]);
+    }
+
+    #[test]
+    fn aligns_lines_in_the_block() {
+        let font = test_font::font();
+        let right = layout_text(&font, "A", &options(Some(1000.), Align::Right));
+        assert_eq!(right.glyphs[0].x, 400.);
+        let center = layout_text(&font, "A\nAA", &options(None, Align::Center));
+        assert_eq!(center.glyphs[0].x, 300.);
+        assert_eq!(center.glyphs[1].x, 0.);
+    }
+}
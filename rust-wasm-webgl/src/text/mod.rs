@@ -0,0 +1,8 @@
+mod layout;
+mod sdf;
+#[cfg(test)]
+mod test_font;
+mod ttf;
+pub use layout::*;
+pub use sdf::*;
+pub use ttf::*;
@@ -0,0 +1,174 @@
+use super::ttf::{Outline, Point, Segment};
+
+/// Single channel signed distance field. 128 is the outline,
+/// higher values are inside the glyph.
+pub struct SdfBitmap {
+    pub width: i32,
+    pub height: i32,
+    pub pixels: Vec<u8>,
+    /// Position of the bitmap's top left corner relative to the glyph origin,
+    /// in pixels with y pointing up
+    pub left: f32,
+    pub top: f32,
+}
+
+const CURVE_STEPS: usize = 8;
+
+/// Rasterizes `outline` scaled by `scale` (pixels per font unit), with distances
+/// saturating at `spread` pixels away from the outline.
+pub fn generate_sdf(outline: &Outline, scale: f32, spread: f32) -> SdfBitmap {
+    let lines = flatten(outline, scale);
+
+    let [x_min, y_min, x_max, y_max] = outline.bounds.map(|v| v * scale);
+    let left = (x_min - spread).floor();
+    let bottom = (y_min - spread).floor();
+    let width = ((x_max + spread).ceil() - left).max(1.) as i32;
+    let height = ((y_max + spread).ceil() - bottom).max(1.) as i32;
+    let top = bottom + height as f32;
+
+    let mut pixels = Vec::with_capacity((width * height) as usize);
+    for row in 0..height {
+        for column in 0..width {
+            let p = Point {
+                x: left + column as f32 + 0.5,
+                y: top - row as f32 - 0.5,
+            };
+
+            let mut distance = f32::MAX;
+            let mut winding = 0;
+            for &(a, b) in &lines {
+                distance = distance.min(distance_to_line(p, a, b));
+                winding += crossing(p, a, b);
+            }
+
+            let signed = if winding != 0 { distance } else { -distance };
+            let value = 0.5 + signed / (2. * spread);
+            pixels.push((value.clamp(0., 1.) * 255.).round() as u8);
+        }
+    }
+
+    SdfBitmap {
+        width,
+        height,
+        pixels,
+        left,
+        top,
+    }
+}
+
+fn flatten(outline: &Outline, scale: f32) -> Vec<(Point, Point)> {
+    let scaled = |p: Point| Point {
+        x: p.x * scale,
+        y: p.y * scale,
+    };
+
+    let mut lines = vec![];
+    for segment in &outline.segments {
+        match *segment {
+            Segment::Line(a, b) => lines.push((scaled(a), scaled(b))),
+            Segment::Quad(a, c, b) => {
+                let (a, c, b) = (scaled(a), scaled(c), scaled(b));
+                let mut previous = a;
+                for step in 1..=CURVE_STEPS {
+                    let t = step as f32 / CURVE_STEPS as f32;
+                    let u = 1. - t;
+                    let next = Point {
+                        x: u * u * a.x + 2. * u * t * c.x + t * t * b.x,
+                        y: u * u * a.y + 2. * u * t * c.y + t * t * b.y,
+                    };
+                    lines.push((previous, next));
+                    previous = next;
+                }
+            }
+        }
+    }
+    lines
+}
+
+fn distance_to_line(p: Point, a: Point, b: Point) -> f32 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq > 0. {
+        (((p.x - a.x) * dx + (p.y - a.y) * dy) / len_sq).clamp(0., 1.)
+    } else {
+        0.
+    };
+    let (x, y) = (a.x + t * dx - p.x, a.y + t * dy - p.y);
+    (x * x + y * y).sqrt()
+}
+
+/// Winding contribution of the edge for a ray going from `p` to +x
+fn crossing(p: Point, a: Point, b: Point) -> i32 {
+    if (a.y <= p.y) == (b.y <= p.y) {
+        return 0;
+    }
+    let x = a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x);
+    if x <= p.x {
+        0
+    } else if b.y > a.y {
+        1
+    } else {
+        -1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(size: f32) -> Outline {
+        let p = |x, y| Point { x, y };
+        Outline {
+            segments: vec![
+                Segment::Line(p(0., 0.), p(size, 0.)),
+                Segment::Line(p(size, 0.), p(size, size)),
+                Segment::Line(p(size, size), p(0., size)),
+                Segment::Line(p(0., size), p(0., 0.)),
+            ],
+            bounds: [0., 0., size, size],
+        }
+    }
+
+    #[test]
+    fn pads_the_bitmap_by_the_spread() {
+        let sdf = generate_sdf(&square(10.), 1., 2.);
+        assert_eq!((sdf.width, sdf.height), (14, 14));
+        assert_eq!((sdf.left, sdf.top), (-2., 12.));
+        assert_eq!(sdf.pixels.len(), 14 * 14);
+    }
+
+    #[test]
+    fn encodes_signed_distances() {
+        let sdf = generate_sdf(&square(10.), 1., 2.);
+        let pixel = |column: i32, row: i32| sdf.pixels[(row * sdf.width + column) as usize];
+        // Center of the square, and a corner of the bitmap outside it
+        assert_eq!(pixel(7, 7), 255);
+        assert_eq!(pixel(0, 0), 0);
+        // Half a pixel inside and outside of the left edge
+        assert_eq!(pixel(2, 7), 159);
+        assert_eq!(pixel(1, 7), 96);
+    }
+
+    #[test]
+    fn scales_the_outline() {
+        let sdf = generate_sdf(&square(10.), 0.5, 1.);
+        assert_eq!((sdf.width, sdf.height), (7, 7));
+    }
+
+    #[test]
+    fn fills_curved_outlines() {
+        let p = |x, y| Point { x, y };
+        let outline = Outline {
+            segments: vec![
+                Segment::Quad(p(0., 0.), p(5., 10.), p(10., 0.)),
+                Segment::Line(p(10., 0.), p(0., 0.)),
+            ],
+            bounds: [0., 0., 10., 5.],
+        };
+        let sdf = generate_sdf(&outline, 1., 1.);
+        let pixel = |column: i32, row: i32| sdf.pixels[(row * sdf.width + column) as usize];
+        // (5.5, 2.5) is under the peak of the curve at y = 5
+        assert!(pixel(6, sdf.height - 4) > 128);
+        assert!(pixel(1, 0) < 128);
+    }
+}
@@ -0,0 +1,219 @@
+//! Handcrafted TrueType font for the tests of the text module.
+//!
+//! Glyphs: 0 `.notdef` (empty), 1 `A` a 500x700 square, 2 `B` glyph 1 moved
+//! by 100 units as a composite, 3 space (empty). `A` then `B` is kerned by -50.
+
+use super::ttf::Font;
+
+pub const UNITS_PER_EM: u16 = 1000;
+pub const ASCENDER: i16 = 800;
+pub const DESCENDER: i16 = -200;
+pub const ADVANCES: [u16; 4] = [600, 600, 700, 250];
+pub const KERNING: i16 = -50;
+
+pub fn font() -> Font {
+    match Font::from_bytes(build(&tables())) {
+        Ok(font) => font,
+        Err(error) => panic!("{}", error),
+    }
+}
+
+/// Tables of the font, to replace some of them before `build`
+pub fn tables() -> Vec<(&'static str, Vec<u8>)> {
+    let square = simple_glyph(&[(0, 0), (500, 0), (500, 700), (0, 700)]);
+    let composite = composite_glyph(&[(1, 100, 0)]);
+    let (glyf, loca) = glyf_and_loca(&[vec![], square, composite, vec![]]);
+
+    let mut hmtx = vec![];
+    for advance in ADVANCES {
+        push_u16(&mut hmtx, advance);
+        push_u16(&mut hmtx, 0);
+    }
+
+    vec![
+        ("cmap", cmap_format_12(&[(0x20, 0x20, 3), (0x41, 0x42, 1)])),
+        ("glyf", glyf),
+        ("head", head(UNITS_PER_EM)),
+        ("hhea", hhea(ADVANCES.len() as u16)),
+        ("hmtx", hmtx),
+        ("kern", kern(1, 2, KERNING)),
+        ("loca", loca),
+        ("maxp", maxp(ADVANCES.len() as u16)),
+    ]
+}
+
+pub fn replace_table(tables: &mut [(&'static str, Vec<u8>)], tag: &str, data: Vec<u8>) {
+    if let Some(table) = tables.iter_mut().find(|(t, _)| *t == tag) {
+        table.1 = data;
+    }
+}
+
+/// `glyf` and short `loca` tables of the glyphs, whose lengths must be even
+pub fn glyf_and_loca(glyphs: &[Vec<u8>]) -> (Vec<u8>, Vec<u8>) {
+    let mut loca = vec![0, 0];
+    for end in glyphs.iter().scan(0, |end, glyph| {
+        *end += glyph.len();
+        Some(*end)
+    }) {
+        push_u16(&mut loca, end as u16 / 2);
+    }
+    (glyphs.concat(), loca)
+}
+
+pub fn build(tables: &[(&'static str, Vec<u8>)]) -> Vec<u8> {
+    let mut font = vec![];
+    push_u32(&mut font, 0x0001_0000);
+    push_u16(&mut font, tables.len() as u16);
+    font.extend_from_slice(&[0; 6]);
+
+    let mut offset = 12 + tables.len() * 16;
+    for (tag, data) in tables {
+        font.extend_from_slice(tag.as_bytes());
+        push_u32(&mut font, 0);
+        push_u32(&mut font, offset as u32);
+        push_u32(&mut font, data.len() as u32);
+        offset += (data.len() + 3) & !3;
+    }
+    for (_, data) in tables {
+        font.extend_from_slice(data);
+        font.resize((font.len() + 3) & !3, 0);
+    }
+    font
+}
+
+pub fn head(units_per_em: u16) -> Vec<u8> {
+    let mut head = vec![0; 54];
+    head[18..20].copy_from_slice(&units_per_em.to_be_bytes());
+    // Short loca offsets
+    head[50..52].copy_from_slice(&0i16.to_be_bytes());
+    head
+}
+
+fn hhea(h_metrics_count: u16) -> Vec<u8> {
+    let mut hhea = vec![0; 36];
+    hhea[4..6].copy_from_slice(&ASCENDER.to_be_bytes());
+    hhea[6..8].copy_from_slice(&DESCENDER.to_be_bytes());
+    hhea[34..36].copy_from_slice(&h_metrics_count.to_be_bytes());
+    hhea
+}
+
+fn maxp(glyphs_count: u16) -> Vec<u8> {
+    let mut maxp = vec![0; 6];
+    maxp[4..6].copy_from_slice(&glyphs_count.to_be_bytes());
+    maxp
+}
+
+/// One contour of on curve points, with 16 bit coordinates
+fn simple_glyph(points: &[(i16, i16)]) -> Vec<u8> {
+    let mut glyph = vec![];
+    push_u16(&mut glyph, 1);
+    glyph.extend_from_slice(&[0; 8]);
+    push_u16(&mut glyph, points.len() as u16 - 1);
+    push_u16(&mut glyph, 0);
+    glyph.extend(points.iter().map(|_| 0x01));
+    for coordinate in [0, 1] {
+        let mut previous = 0;
+        for point in points {
+            let value = if coordinate == 0 { point.0 } else { point.1 };
+            push_u16(&mut glyph, (value - previous) as u16);
+            previous = value;
+        }
+    }
+    glyph.resize((glyph.len() + 1) & !1, 0);
+    glyph
+}
+
+/// Components of (glyph, dx, dy)
+pub fn composite_glyph(components: &[(u16, i16, i16)]) -> Vec<u8> {
+    const ARGS_ARE_WORDS_AND_XY_VALUES: u16 = 0x0003;
+    const MORE_COMPONENTS: u16 = 0x0020;
+    let mut glyph = vec![];
+    push_u16(&mut glyph, -1i16 as u16);
+    glyph.extend_from_slice(&[0; 8]);
+    for (i, &(component, dx, dy)) in components.iter().enumerate() {
+        let more = if i + 1 < components.len() {
+            MORE_COMPONENTS
+        } else {
+            0
+        };
+        push_u16(&mut glyph, ARGS_ARE_WORDS_AND_XY_VALUES | more);
+        push_u16(&mut glyph, component);
+        push_u16(&mut glyph, dx as u16);
+        push_u16(&mut glyph, dy as u16);
+    }
+    glyph
+}
+
+/// Windows unicode full repertoire subtable with (start, end, first glyph) groups
+pub fn cmap_format_12(groups: &[(u32, u32, u32)]) -> Vec<u8> {
+    let mut cmap = cmap_header(10);
+    push_u16(&mut cmap, 12);
+    push_u16(&mut cmap, 0);
+    push_u32(&mut cmap, 16 + groups.len() as u32 * 12);
+    push_u32(&mut cmap, 0);
+    push_u32(&mut cmap, groups.len() as u32);
+    for &(start, end, glyph) in groups {
+        push_u32(&mut cmap, start);
+        push_u32(&mut cmap, end);
+        push_u32(&mut cmap, glyph);
+    }
+    cmap
+}
+
+/// Windows unicode BMP subtable with (start, end, delta) segments
+pub fn cmap_format_4(segments: &[(u16, u16, u16)]) -> Vec<u8> {
+    let mut cmap = cmap_header(1);
+    push_u16(&mut cmap, 4);
+    push_u16(&mut cmap, 16 + segments.len() as u16 * 8);
+    push_u16(&mut cmap, 0);
+    push_u16(&mut cmap, segments.len() as u16 * 2);
+    cmap.extend_from_slice(&[0; 6]);
+    for &(_, end, _) in segments {
+        push_u16(&mut cmap, end);
+    }
+    push_u16(&mut cmap, 0);
+    for &(start, _, _) in segments {
+        push_u16(&mut cmap, start);
+    }
+    for &(_, _, delta) in segments {
+        push_u16(&mut cmap, delta);
+    }
+    for _ in segments {
+        push_u16(&mut cmap, 0);
+    }
+    cmap
+}
+
+fn cmap_header(encoding: u16) -> Vec<u8> {
+    let mut cmap = vec![];
+    push_u16(&mut cmap, 0);
+    push_u16(&mut cmap, 1);
+    push_u16(&mut cmap, 3);
+    push_u16(&mut cmap, encoding);
+    push_u32(&mut cmap, 12);
+    cmap
+}
+
+fn kern(left: u16, right: u16, value: i16) -> Vec<u8> {
+    let mut kern = vec![];
+    push_u16(&mut kern, 0);
+    push_u16(&mut kern, 1);
+    push_u16(&mut kern, 0);
+    push_u16(&mut kern, 20);
+    // Horizontal, format 0
+    push_u16(&mut kern, 0x0001);
+    push_u16(&mut kern, 1);
+    kern.extend_from_slice(&[0; 6]);
+    push_u16(&mut kern, left);
+    push_u16(&mut kern, right);
+    push_u16(&mut kern, value as u16);
+    kern
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
@@ -0,0 +1,817 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+
+/// Components of a glyph at most, counting those of nested composites, so a
+/// crafted font can't make an outline grow exponentially with the nesting
+const MAX_COMPONENTS: u32 = 1024;
+
+/// TrueType (glyf outlines) font. Kerning is read from the GPOS `kern` feature
+/// pair adjustments and from the legacy `kern` table.
+pub struct Font {
+    data: Vec<u8>,
+    glyf: usize,
+    loca: usize,
+    hmtx: usize,
+    long_loca: bool,
+    glyphs_count: u16,
+    h_metrics_count: u16,
+    units_per_em: f32,
+    ascender: f32,
+    descender: f32,
+    line_gap: f32,
+    cmap: HashMap<u32, u16>,
+    kern_pairs: HashMap<(u16, u16), i16>,
+    gpos_pair_subtables: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Quadratic outline in font units, contours are closed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    Line(Point, Point),
+    Quad(Point, Point, Point),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Outline {
+    pub segments: Vec<Segment>,
+    /// [x min, y min, x max, y max]
+    pub bounds: [f32; 4],
+}
+
+fn malformed(table: &str) -> Error {
+    Error::Font(format!("Malformed font: invalid `{}` table", table))
+}
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Font, Error> {
+        let tables = read_table_directory(&data).ok_or_else(|| malformed("table directory"))?;
+        let table = |tag: &str| {
+            tables
+                .get(tag)
+                .copied()
+                .ok_or_else(|| Error::Font(format!("Malformed font: missing `{}` table", tag)))
+        };
+
+        let head = table("head")?;
+        let maxp = table("maxp")?;
+        let hhea = table("hhea")?;
+
+        let units_per_em = read_u16(&data, head + 18)
+            .filter(|&units| units > 0)
+            .ok_or_else(|| malformed("head"))?;
+        let long_loca = read_i16(&data, head + 50).ok_or_else(|| malformed("head"))? == 1;
+        let glyphs_count = read_u16(&data, maxp + 4).ok_or_else(|| malformed("maxp"))?;
+
+        let hhea_field = |offset| read_i16(&data, hhea + offset).ok_or_else(|| malformed("hhea"));
+
+        let mut font = Font {
+            glyf: table("glyf")?,
+            loca: table("loca")?,
+            hmtx: table("hmtx")?,
+            long_loca,
+            glyphs_count,
+            h_metrics_count: hhea_field(34)? as u16,
+            units_per_em: units_per_em as f32,
+            ascender: hhea_field(4)? as f32,
+            descender: hhea_field(6)? as f32,
+            line_gap: hhea_field(8)? as f32,
+            cmap: read_cmap(&data, table("cmap")?).ok_or_else(|| malformed("cmap"))?,
+            kern_pairs: HashMap::new(),
+            gpos_pair_subtables: vec![],
+            data: vec![],
+        };
+
+        if let Some(&kern) = tables.get("kern") {
+            font.kern_pairs = read_kern(&data, kern).ok_or_else(|| malformed("kern"))?;
+        }
+        if let Some(&gpos) = tables.get("GPOS") {
+            font.gpos_pair_subtables =
+                read_gpos_pair_subtables(&data, gpos).ok_or_else(|| malformed("GPOS"))?;
+        }
+
+        font.data = data;
+        Ok(font)
+    }
+
+    pub fn units_per_em(&self) -> f32 {
+        self.units_per_em
+    }
+
+    pub fn ascender(&self) -> f32 {
+        self.ascender
+    }
+
+    pub fn descender(&self) -> f32 {
+        self.descender
+    }
+
+    /// Baseline to baseline distance in font units
+    pub fn line_height(&self) -> f32 {
+        self.ascender - self.descender + self.line_gap
+    }
+
+    pub fn glyph_index(&self, c: char) -> Option<u16> {
+        self.cmap.get(&(c as u32)).copied()
+    }
+
+    pub fn advance(&self, glyph: u16) -> f32 {
+        let index = glyph.min(self.h_metrics_count.saturating_sub(1)) as usize;
+        read_u16(&self.data, self.hmtx + index * 4).unwrap_or(0) as f32
+    }
+
+    /// Horizontal adjustment to add to the advance of `left` when followed by `right`
+    pub fn kerning(&self, left: u16, right: u16) -> f32 {
+        for &subtable in &self.gpos_pair_subtables {
+            if let Some(value) = read_pair_adjustment(&self.data, subtable, left, right) {
+                return value as f32;
+            }
+        }
+        self.kern_pairs
+            .get(&(left, right))
+            .map_or(0., |&value| value as f32)
+    }
+
+    pub fn outline(&self, glyph: u16) -> Result<Outline, Error> {
+        let mut outline = Outline::default();
+        let mut components = 0;
+        self.append_outline(
+            glyph,
+            &[1., 0., 0., 1., 0., 0.],
+            &mut outline,
+            0,
+            &mut components,
+        )
+        .ok_or_else(|| malformed("glyf"))?;
+
+        let mut bounds = [f32::MAX, f32::MAX, f32::MIN, f32::MIN];
+        for segment in &outline.segments {
+            let points: &[Point] = match segment {
+                Segment::Line(a, b) => &[*a, *b],
+                Segment::Quad(a, b, c) => &[*a, *b, *c],
+            };
+            for p in points {
+                bounds = [
+                    bounds[0].min(p.x),
+                    bounds[1].min(p.y),
+                    bounds[2].max(p.x),
+                    bounds[3].max(p.y),
+                ];
+            }
+        }
+        if !outline.segments.is_empty() {
+            outline.bounds = bounds;
+        }
+        Ok(outline)
+    }
+
+    fn glyph_range(&self, glyph: u16) -> Option<(usize, usize)> {
+        if glyph >= self.glyphs_count {
+            return None;
+        }
+        let glyph = glyph as usize;
+        let (start, end) = if self.long_loca {
+            (
+                read_u32(&self.data, self.loca + glyph * 4)? as usize,
+                read_u32(&self.data, self.loca + glyph * 4 + 4)? as usize,
+            )
+        } else {
+            (
+                read_u16(&self.data, self.loca + glyph * 2)? as usize * 2,
+                read_u16(&self.data, self.loca + glyph * 2 + 2)? as usize * 2,
+            )
+        };
+        Some((self.glyf.checked_add(start)?, self.glyf.checked_add(end)?))
+    }
+
+    /// `transform` is [a, b, c, d, dx, dy] with x' = a*x + c*y + dx and y' = b*x + d*y + dy
+    fn append_outline(
+        &self,
+        glyph: u16,
+        transform: &[f32; 6],
+        outline: &mut Outline,
+        depth: u32,
+        components: &mut u32,
+    ) -> Option<()> {
+        let (start, end) = self.glyph_range(glyph)?;
+        if start == end || depth > 8 {
+            return Some(());
+        }
+
+        let contours_count = read_i16(&self.data, start)?;
+        if contours_count >= 0 {
+            self.append_simple_outline(start, contours_count as usize, transform, outline)
+        } else {
+            self.append_composite_outline(start, transform, outline, depth, components)
+        }
+    }
+
+    fn append_simple_outline(
+        &self,
+        start: usize,
+        contours_count: usize,
+        transform: &[f32; 6],
+        outline: &mut Outline,
+    ) -> Option<()> {
+        const ON_CURVE: u8 = 0x01;
+        const X_SHORT: u8 = 0x02;
+        const Y_SHORT: u8 = 0x04;
+        const REPEAT: u8 = 0x08;
+        const X_SAME_OR_POSITIVE: u8 = 0x10;
+        const Y_SAME_OR_POSITIVE: u8 = 0x20;
+
+        let data = &self.data;
+        let mut offset = start + 10;
+
+        let mut contour_ends = Vec::with_capacity(contours_count);
+        for _ in 0..contours_count {
+            contour_ends.push(read_u16(data, offset)? as usize);
+            offset += 2;
+        }
+        let points_count = contour_ends.last().map_or(0, |end| end + 1);
+
+        let instructions_len = read_u16(data, offset)? as usize;
+        offset += 2 + instructions_len;
+
+        let mut flags = Vec::with_capacity(points_count);
+        while flags.len() < points_count {
+            let flag = *data.get(offset)?;
+            offset += 1;
+            flags.push(flag);
+            if flag & REPEAT != 0 {
+                let repeat = *data.get(offset)?;
+                offset += 1;
+                for _ in 0..repeat {
+                    flags.push(flag);
+                }
+            }
+        }
+        flags.truncate(points_count);
+
+        let mut read_coords = |short: u8, same_or_positive: u8| -> Option<Vec<f32>> {
+            let mut coords = Vec::with_capacity(points_count);
+            let mut value = 0i32;
+            for flag in &flags {
+                if flag & short != 0 {
+                    let delta = *data.get(offset)? as i32;
+                    offset += 1;
+                    value += if flag & same_or_positive != 0 {
+                        delta
+                    } else {
+                        -delta
+                    };
+                } else if flag & same_or_positive == 0 {
+                    value += read_i16(data, offset)? as i32;
+                    offset += 2;
+                }
+                coords.push(value as f32);
+            }
+            Some(coords)
+        };
+        let xs = read_coords(X_SHORT, X_SAME_OR_POSITIVE)?;
+        let ys = read_coords(Y_SHORT, Y_SAME_OR_POSITIVE)?;
+
+        let mut contour_start = 0;
+        for &contour_end in &contour_ends {
+            if contour_end < contour_start || contour_end >= points_count {
+                return None;
+            }
+            let contour: Vec<(Point, bool)> = (contour_start..=contour_end)
+                .map(|i| {
+                    let p = Point {
+                        x: transform[0] * xs[i] + transform[2] * ys[i] + transform[4],
+                        y: transform[1] * xs[i] + transform[3] * ys[i] + transform[5],
+                    };
+                    (p, flags[i] & ON_CURVE != 0)
+                })
+                .collect();
+            append_contour(&contour, &mut outline.segments);
+            contour_start = contour_end + 1;
+        }
+        Some(())
+    }
+
+    fn append_composite_outline(
+        &self,
+        start: usize,
+        transform: &[f32; 6],
+        outline: &mut Outline,
+        depth: u32,
+        components: &mut u32,
+    ) -> Option<()> {
+        const ARGS_ARE_WORDS: u16 = 0x0001;
+        const ARGS_ARE_XY_VALUES: u16 = 0x0002;
+        const HAVE_SCALE: u16 = 0x0008;
+        const MORE_COMPONENTS: u16 = 0x0020;
+        const HAVE_X_AND_Y_SCALE: u16 = 0x0040;
+        const HAVE_TWO_BY_TWO: u16 = 0x0080;
+
+        let data = &self.data;
+        let mut offset = start + 10;
+        loop {
+            *components += 1;
+            if *components > MAX_COMPONENTS {
+                return None;
+            }
+            let flags = read_u16(data, offset)?;
+            let glyph = read_u16(data, offset + 2)?;
+            offset += 4;
+
+            let (dx, dy) = if flags & ARGS_ARE_WORDS != 0 {
+                offset += 4;
+                (
+                    read_i16(data, offset - 4)? as f32,
+                    read_i16(data, offset - 2)? as f32,
+                )
+            } else {
+                offset += 2;
+                (
+                    *data.get(offset - 2)? as i8 as f32,
+                    *data.get(offset - 1)? as i8 as f32,
+                )
+            };
+            // Anchor point matching is not supported, those components are left in place
+            let (dx, dy) = if flags & ARGS_ARE_XY_VALUES != 0 {
+                (dx, dy)
+            } else {
+                (0., 0.)
+            };
+
+            let f2dot14 = |offset| Some(read_i16(data, offset)? as f32 / 16384.);
+            let [a, b, c, d] = if flags & HAVE_SCALE != 0 {
+                offset += 2;
+                let scale = f2dot14(offset - 2)?;
+                [scale, 0., 0., scale]
+            } else if flags & HAVE_X_AND_Y_SCALE != 0 {
+                offset += 4;
+                [f2dot14(offset - 4)?, 0., 0., f2dot14(offset - 2)?]
+            } else if flags & HAVE_TWO_BY_TWO != 0 {
+                offset += 8;
+                [
+                    f2dot14(offset - 8)?,
+                    f2dot14(offset - 6)?,
+                    f2dot14(offset - 4)?,
+                    f2dot14(offset - 2)?,
+                ]
+            } else {
+                [1., 0., 0., 1.]
+            };
+
+            let t = transform;
+            let component = [
+                t[0] * a + t[2] * b,
+                t[1] * a + t[3] * b,
+                t[0] * c + t[2] * d,
+                t[1] * c + t[3] * d,
+                t[0] * dx + t[2] * dy + t[4],
+                t[1] * dx + t[3] * dy + t[5],
+            ];
+            self.append_outline(glyph, &component, outline, depth + 1, components)?;
+
+            if flags & MORE_COMPONENTS == 0 {
+                return Some(());
+            }
+        }
+    }
+}
+
+/// Converts a closed TrueType contour (on and off curve points) into segments
+fn append_contour(contour: &[(Point, bool)], segments: &mut Vec<Segment>) {
+    if contour.len() < 2 {
+        return;
+    }
+    let midpoint = |a: Point, b: Point| Point {
+        x: (a.x + b.x) / 2.,
+        y: (a.y + b.y) / 2.,
+    };
+
+    // Start from an on curve point, or the implied one between two off curve points
+    let first_on = contour.iter().position(|(_, on)| *on);
+    let (start, rotation) = match first_on {
+        Some(index) => (contour[index].0, index),
+        None => (midpoint(contour[0].0, contour[1].0), 0),
+    };
+
+    let mut current = start;
+    let mut control: Option<Point> = None;
+    for i in 1..=contour.len() {
+        let (point, on_curve) = contour[(rotation + i) % contour.len()];
+        match (control, on_curve) {
+            (None, true) => {
+                segments.push(Segment::Line(current, point));
+                current = point;
+            }
+            (None, false) => control = Some(point),
+            (Some(c), true) => {
+                segments.push(Segment::Quad(current, c, point));
+                current = point;
+                control = None;
+            }
+            (Some(c), false) => {
+                let implied = midpoint(c, point);
+                segments.push(Segment::Quad(current, c, implied));
+                current = implied;
+                control = Some(point);
+            }
+        }
+    }
+    match control {
+        Some(c) => segments.push(Segment::Quad(current, c, start)),
+        None if current != start => segments.push(Segment::Line(current, start)),
+        None => {}
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *data.get(offset)?,
+        *data.get(offset.checked_add(1)?)?,
+    ]))
+}
+
+fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    Some(read_u16(data, offset)? as i16)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(((read_u16(data, offset)? as u32) << 16) | read_u16(data, offset.checked_add(2)?)? as u32)
+}
+
+fn read_tag(data: &[u8], offset: usize) -> Option<&str> {
+    std::str::from_utf8(data.get(offset..offset.checked_add(4)?)?).ok()
+}
+
+fn read_table_directory(data: &[u8]) -> Option<HashMap<String, usize>> {
+    let tables_count = read_u16(data, 4)? as usize;
+    let mut tables = HashMap::new();
+    for i in 0..tables_count {
+        let record = 12 + i * 16;
+        let tag = read_tag(data, record)?;
+        let offset = read_u32(data, record + 8)? as usize;
+        let len = read_u32(data, record + 12)? as usize;
+        if offset.checked_add(len)? > data.len() {
+            return None;
+        }
+        tables.insert(tag.to_string(), offset);
+    }
+    Some(tables)
+}
+
+/// Reads the best unicode subtable (format 12 or 4)
+fn read_cmap(data: &[u8], cmap: usize) -> Option<HashMap<u32, u16>> {
+    let subtables_count = read_u16(data, cmap + 2)? as usize;
+    let mut best: Option<(u16, usize)> = None;
+    for i in 0..subtables_count {
+        let record = cmap + 4 + i * 8;
+        let platform = read_u16(data, record)?;
+        let encoding = read_u16(data, record + 2)?;
+        let subtable = cmap.checked_add(read_u32(data, record + 4)? as usize)?;
+        let unicode = platform == 0 || (platform == 3 && (encoding == 1 || encoding == 10));
+        let format = read_u16(data, subtable)?;
+        if unicode && (format == 4 || format == 12) && best.is_none_or(|(f, _)| format > f) {
+            best = Some((format, subtable));
+        }
+    }
+
+    let mut map = HashMap::new();
+    match best {
+        Some((12, subtable)) => {
+            let groups_count = read_u32(data, subtable + 12)? as usize;
+            for i in 0..groups_count {
+                let group = subtable.checked_add(16 + i.checked_mul(12)?)?;
+                let start = read_u32(data, group)?;
+                let end = read_u32(data, group + 4)?;
+                let glyph = read_u32(data, group + 8)?;
+                // Glyph ids past u16 don't exist, a malformed group can't map more
+                for c in start..=end.min(start.saturating_add(0xFFFF)) {
+                    match glyph
+                        .checked_add(c - start)
+                        .and_then(|glyph| u16::try_from(glyph).ok())
+                    {
+                        Some(glyph) => map.insert(c, glyph),
+                        None => break,
+                    };
+                }
+            }
+        }
+        Some((_, subtable)) => {
+            let segments_count = read_u16(data, subtable + 6)? as usize / 2;
+            let ends = subtable + 14;
+            let starts = ends + segments_count * 2 + 2;
+            let deltas = starts + segments_count * 2;
+            let range_offsets = deltas + segments_count * 2;
+            for i in 0..segments_count {
+                let end = read_u16(data, ends + i * 2)?;
+                let start = read_u16(data, starts + i * 2)?;
+                let delta = read_u16(data, deltas + i * 2)?;
+                let range_offset = read_u16(data, range_offsets + i * 2)? as usize;
+                for c in start..=end {
+                    if c == 0xFFFF {
+                        break;
+                    }
+                    let glyph = if range_offset == 0 {
+                        c.wrapping_add(delta)
+                    } else {
+                        let address =
+                            range_offsets + i * 2 + range_offset + (c - start) as usize * 2;
+                        match read_u16(data, address)? {
+                            0 => 0,
+                            glyph => glyph.wrapping_add(delta),
+                        }
+                    };
+                    if glyph != 0 {
+                        map.insert(c as u32, glyph);
+                    }
+                }
+            }
+        }
+        None => return None,
+    }
+    Some(map)
+}
+
+/// Format 0 subtables of the legacy `kern` table
+fn read_kern(data: &[u8], kern: usize) -> Option<HashMap<(u16, u16), i16>> {
+    let mut pairs = HashMap::new();
+    let subtables_count = read_u16(data, kern + 2)?;
+    let mut subtable = kern + 4;
+    for _ in 0..subtables_count {
+        let len = read_u16(data, subtable + 2)? as usize;
+        let coverage = read_u16(data, subtable + 4)?;
+        // Horizontal, format 0
+        if coverage & 0x1 != 0 && coverage >> 8 == 0 {
+            let pairs_count = read_u16(data, subtable + 6)? as usize;
+            for i in 0..pairs_count {
+                let pair = subtable + 14 + i * 6;
+                pairs.insert(
+                    (read_u16(data, pair)?, read_u16(data, pair + 2)?),
+                    read_i16(data, pair + 4)?,
+                );
+            }
+        }
+        subtable += len;
+    }
+    Some(pairs)
+}
+
+/// Offsets of the pair adjustment subtables used by the `kern` feature
+fn read_gpos_pair_subtables(data: &[u8], gpos: usize) -> Option<Vec<usize>> {
+    const PAIR_ADJUSTMENT: u16 = 2;
+    const EXTENSION: u16 = 9;
+
+    let features = gpos + read_u16(data, gpos + 6)? as usize;
+    let lookups = gpos + read_u16(data, gpos + 8)? as usize;
+
+    let mut lookup_indices = vec![];
+    let features_count = read_u16(data, features)? as usize;
+    for i in 0..features_count {
+        let record = features + 2 + i * 6;
+        if read_tag(data, record)? == "kern" {
+            let feature = features + read_u16(data, record + 4)? as usize;
+            let indices_count = read_u16(data, feature + 2)? as usize;
+            for j in 0..indices_count {
+                lookup_indices.push(read_u16(data, feature + 4 + j * 2)?);
+            }
+        }
+    }
+    lookup_indices.sort_unstable();
+    lookup_indices.dedup();
+
+    let mut subtables = vec![];
+    for index in lookup_indices {
+        let lookup = lookups + read_u16(data, lookups + 2 + index as usize * 2)? as usize;
+        let lookup_type = read_u16(data, lookup)?;
+        let subtables_count = read_u16(data, lookup + 4)? as usize;
+        for i in 0..subtables_count {
+            let subtable = lookup + read_u16(data, lookup + 6 + i * 2)? as usize;
+            if lookup_type == PAIR_ADJUSTMENT {
+                subtables.push(subtable);
+            } else if lookup_type == EXTENSION && read_u16(data, subtable + 2)? == PAIR_ADJUSTMENT {
+                subtables.push(subtable.checked_add(read_u32(data, subtable + 4)? as usize)?);
+            }
+        }
+    }
+    Some(subtables)
+}
+
+/// X advance adjustment of the first glyph, if the subtable covers the pair
+fn read_pair_adjustment(data: &[u8], subtable: usize, left: u16, right: u16) -> Option<i16> {
+    const X_ADVANCE: u16 = 0x0004;
+
+    let format = read_u16(data, subtable)?;
+    let coverage = subtable + read_u16(data, subtable + 2)? as usize;
+    let value_format_1 = read_u16(data, subtable + 4)?;
+    let value_format_2 = read_u16(data, subtable + 6)?;
+    if value_format_1 & X_ADVANCE == 0 {
+        return None;
+    }
+    let x_advance_offset = (value_format_1 & (X_ADVANCE - 1)).count_ones() as usize * 2;
+    let value_1_len = value_format_1.count_ones() as usize * 2;
+    let value_2_len = value_format_2.count_ones() as usize * 2;
+
+    let coverage_index = read_coverage_index(data, coverage, left)?;
+    match format {
+        1 => {
+            let pair_set = subtable + read_u16(data, subtable + 10 + coverage_index * 2)? as usize;
+            let pairs_count = read_u16(data, pair_set)? as usize;
+            let record_len = 2 + value_1_len + value_2_len;
+            // Records are sorted by the second glyph
+            let (mut low, mut high) = (0, pairs_count);
+            while low < high {
+                let middle = (low + high) / 2;
+                let record = pair_set + 2 + middle * record_len;
+                let second = read_u16(data, record)?;
+                if second == right {
+                    return read_i16(data, record + 2 + x_advance_offset);
+                } else if second < right {
+                    low = middle + 1;
+                } else {
+                    high = middle;
+                }
+            }
+            None
+        }
+        2 => {
+            let class_def_1 = subtable + read_u16(data, subtable + 8)? as usize;
+            let class_def_2 = subtable + read_u16(data, subtable + 10)? as usize;
+            let class_2_count = read_u16(data, subtable + 14)? as usize;
+            let class_1 = read_class(data, class_def_1, left)? as usize;
+            let class_2 = read_class(data, class_def_2, right)? as usize;
+            let record =
+                subtable + 16 + (class_1 * class_2_count + class_2) * (value_1_len + value_2_len);
+            match read_i16(data, record + x_advance_offset)? {
+                0 => None,
+                value => Some(value),
+            }
+        }
+        _ => None,
+    }
+}
+
+fn read_coverage_index(data: &[u8], coverage: usize, glyph: u16) -> Option<usize> {
+    let count = read_u16(data, coverage + 2)? as usize;
+    match read_u16(data, coverage)? {
+        1 => (0..count).find(|&i| read_u16(data, coverage + 4 + i * 2) == Some(glyph)),
+        2 => (0..count).find_map(|i| {
+            let range = coverage + 4 + i * 6;
+            let start = read_u16(data, range)?;
+            let end = read_u16(data, range + 2)?;
+            if (start..=end).contains(&glyph) {
+                Some(read_u16(data, range + 4)? as usize + (glyph - start) as usize)
+            } else {
+                None
+            }
+        }),
+        _ => None,
+    }
+}
+
+fn read_class(data: &[u8], class_def: usize, glyph: u16) -> Option<u16> {
+    match read_u16(data, class_def)? {
+        1 => {
+            let start = read_u16(data, class_def + 2)?;
+            let count = read_u16(data, class_def + 4)?;
+            if glyph >= start && glyph - start < count {
+                read_u16(data, class_def + 6 + (glyph - start) as usize * 2)
+            } else {
+                Some(0)
+            }
+        }
+        2 => {
+            let count = read_u16(data, class_def + 2)? as usize;
+            for i in 0..count {
+                let range = class_def + 4 + i * 6;
+                if (read_u16(data, range)?..=read_u16(data, range + 2)?).contains(&glyph) {
+                    return read_u16(data, range + 4);
+                }
+            }
+            Some(0)
+        }
+        _ => Some(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text::test_font::{self, *};
+
+    #[test]
+    fn reads_metrics_and_cmap() {
+        let font = test_font::font();
+        assert_eq!(font.units_per_em(), UNITS_PER_EM as f32);
+        assert_eq!(font.ascender(), ASCENDER as f32);
+        assert_eq!(font.descender(), DESCENDER as f32);
+        assert_eq!(font.line_height(), 1000.);
+        assert_eq!(font.glyph_index('A'), Some(1));
+        assert_eq!(font.glyph_index('B'), Some(2));
+        assert_eq!(font.glyph_index(' '), Some(3));
+        assert_eq!(font.glyph_index('C'), None);
+        assert_eq!(font.advance(2), ADVANCES[2] as f32);
+    }
+
+    #[test]
+    fn reads_cmap_format_4() {
+        let mut tables = test_font::tables();
+        let segments = [
+            (0x20, 0x20, 3u16.wrapping_sub(0x20)),
+            (0x41, 0x42, 1u16.wrapping_sub(0x41)),
+            (0xFFFF, 0xFFFF, 1),
+        ];
+        replace_table(&mut tables, "cmap", cmap_format_4(&segments));
+        let font = Font::from_bytes(build(&tables)).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(font.glyph_index('A'), Some(1));
+        assert_eq!(font.glyph_index('B'), Some(2));
+        assert_eq!(font.glyph_index(' '), Some(3));
+        assert_eq!(font.glyph_index('C'), None);
+    }
+
+    #[test]
+    fn reads_simple_and_composite_outlines() {
+        let font = test_font::font();
+        let square = font.outline(1).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(square.segments.len(), 4);
+        assert!(square
+            .segments
+            .iter()
+            .all(|segment| matches!(segment, Segment::Line(..))));
+        assert_eq!(square.bounds, [0., 0., 500., 700.]);
+
+        let moved = font.outline(2).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(moved.segments.len(), 4);
+        assert_eq!(moved.bounds, [100., 0., 600., 700.]);
+
+        let empty = font.outline(0).unwrap_or_else(|e| panic!("{}", e));
+        assert!(empty.segments.is_empty());
+        assert!(font.outline(4).is_err());
+    }
+
+    #[test]
+    fn caps_the_components_of_nested_composites() {
+        let mut tables = test_font::tables();
+        // 8 copies of itself at each of the 8 nesting levels
+        let recursive = composite_glyph(&[(2, 0, 0); 8]);
+        let (glyf, loca) = glyf_and_loca(&[vec![], vec![], recursive, vec![]]);
+        replace_table(&mut tables, "glyf", glyf);
+        replace_table(&mut tables, "loca", loca);
+        let font = Font::from_bytes(build(&tables)).unwrap_or_else(|e| panic!("{}", e));
+        assert!(matches!(font.outline(2), Err(Error::Font(_))));
+    }
+
+    #[test]
+    fn reads_kern_pairs() {
+        let font = test_font::font();
+        assert_eq!(font.kerning(1, 2), KERNING as f32);
+        assert_eq!(font.kerning(2, 1), 0.);
+    }
+
+    #[test]
+    fn rejects_zero_units_per_em() {
+        let mut tables = test_font::tables();
+        replace_table(&mut tables, "head", head(0));
+        assert!(matches!(
+            Font::from_bytes(build(&tables)),
+            Err(Error::Font(_))
+        ));
+    }
+
+    #[test]
+    fn clamps_format_12_groups_at_the_end_of_the_ranges() {
+        let mut tables = test_font::tables();
+        let groups = [
+            (0xFFFF_FFF0, 0xFFFF_FFFF, 0xFFFF_FFFF),
+            (0x41, 0x0100_0000, 0xFFF0),
+        ];
+        replace_table(&mut tables, "cmap", cmap_format_12(&groups));
+        let font = Font::from_bytes(build(&tables)).unwrap_or_else(|e| panic!("{}", e));
+        assert_eq!(font.glyph_index('A'), Some(0xFFF0));
+        assert_eq!(font.glyph_index('P'), Some(0xFFFF));
+        assert_eq!(font.glyph_index('Q'), None);
+    }
+
+    #[test]
+    fn truncated_fonts_fail_without_panicking() {
+        let data = build(&test_font::tables());
+        for len in 0..data.len() {
+            if let Ok(font) = Font::from_bytes(data[..len].to_vec()) {
+                for glyph in 0..4 {
+                    let _ = font.outline(glyph);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_tables_past_the_end() {
+        let mut data = build(&test_font::tables());
+        // Offset of the first table record
+        data[20..24].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(Font::from_bytes(data).is_err());
+    }
+}
@@ -0,0 +1,290 @@
+use std::{cell::RefCell, collections::VecDeque};
+
+use crate::{
+    clock::{BrowserClock, Clock},
+    error::{self, Error},
+    stats::json_string,
+};
+
+/// A named phase, ended when the returned guard is dropped. Spans can nest.
+///
+/// ```ignore
+/// let _span = trace::span!("setup");
+/// ```
+#[allow(unused_macros)]
+macro_rules! span {
+    ($name:expr) => {
+        crate::trace::Span::begin($name)
+    };
+}
+#[allow(unused_imports)]
+pub(crate) use span;
+
+#[derive(Debug, Clone, Copy)]
+struct TraceEvent {
+    name: &'static str,
+    /// Seconds of the trace clock
+    start: f64,
+    duration: f64,
+}
+
+struct Tracer {
+    enabled: bool,
+    user_timing: bool,
+    clock: Box<dyn Clock>,
+    frame_count: u32,
+    /// Makes the user timing marks of the spans unique, nested spans can share a name
+    span_count: u32,
+    current: Vec<TraceEvent>,
+    frames: VecDeque<(u32, Vec<TraceEvent>)>,
+    max_frames: usize,
+}
+
+thread_local! {
+    static TRACER: RefCell<Tracer> = RefCell::new(Tracer {
+        enabled: false,
+        user_timing: true,
+        clock: Box::new(BrowserClock),
+        frame_count: 0,
+        span_count: 0,
+        current: vec![],
+        frames: VecDeque::new(),
+        max_frames: 120,
+    });
+}
+
+/// Disabled by default, spans cost a clock read each when enabled
+pub fn set_enabled(enabled: bool) {
+    TRACER.with(|tracer| tracer.borrow_mut().enabled = enabled);
+}
+
+/// Also emits `performance.mark` and `performance.measure` for each span so
+/// they show in a recording of the devtools Performance panel, enabled by default.
+/// They are cleared right away, so `performance.getEntriesByType` doesn't list
+/// them, use `chrome_trace` to read the spans from code.
+pub fn set_user_timing(user_timing: bool) {
+    TRACER.with(|tracer| tracer.borrow_mut().user_timing = user_timing);
+}
+
+/// Frames kept for `chrome_trace`
+pub fn set_max_frames(max_frames: usize) {
+    TRACER.with(|tracer| {
+        let mut tracer = tracer.borrow_mut();
+        tracer.max_frames = max_frames.max(1);
+        while tracer.frames.len() > tracer.max_frames {
+            tracer.frames.pop_front();
+        }
+    });
+}
+
+pub fn set_clock(clock: impl Clock + 'static) {
+    TRACER.with(|tracer| tracer.borrow_mut().clock = Box::new(clock));
+}
+
+/// Keeps the spans ended since the last call as a frame, called by the draw loops
+pub fn end_frame() {
+    TRACER.with(|tracer| {
+        let mut tracer = tracer.borrow_mut();
+        if !tracer.enabled && tracer.current.is_empty() {
+            return;
+        }
+        tracer.frame_count += 1;
+        let events = std::mem::take(&mut tracer.current);
+        let frame = tracer.frame_count;
+        if tracer.frames.len() == tracer.max_frames {
+            tracer.frames.pop_front();
+        }
+        tracer.frames.push_back((frame, events));
+    });
+}
+
+pub fn clear() {
+    TRACER.with(|tracer| {
+        let mut tracer = tracer.borrow_mut();
+        tracer.current.clear();
+        tracer.frames.clear();
+    });
+}
+
+/// The kept frames in the Chrome Trace Event Format, to load in `about://tracing`
+/// or the Performance panel of the devtools
+pub fn chrome_trace() -> String {
+    TRACER.with(|tracer| {
+        let tracer = tracer.borrow();
+        let events: Vec<String> = tracer
+            .frames
+            .iter()
+            .flat_map(|(frame, events)| events.iter().map(move |event| (frame, event)))
+            .map(|(frame, event)| {
+                format!(
+                    r#"{{"name":{},"cat":"frame","ph":"X","ts":{},"dur":{},"pid":1,"tid":1,"args":{{"frame":{}}}}}"#,
+                    json_string(event.name),
+                    (event.start * 1e6).round(),
+                    (event.duration * 1e6).round(),
+                    frame
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"traceEvents":[{}],"displayTimeUnit":"ms"}}"#,
+            events.join(",")
+        )
+    })
+}
+
+/// Guard of `span!`
+pub struct Span {
+    name: &'static str,
+    /// None when tracing is disabled
+    start: Option<f64>,
+    /// Name of the `performance.mark` of the start, when user timing is enabled
+    start_mark: Option<String>,
+}
+
+impl Span {
+    pub fn begin(name: &'static str) -> Span {
+        TRACER.with(|tracer| {
+            let mut tracer = tracer.borrow_mut();
+            if !tracer.enabled {
+                return Span {
+                    name,
+                    start: None,
+                    start_mark: None,
+                };
+            }
+            let start_mark = tracer.user_timing.then(|| {
+                tracer.span_count = tracer.span_count.wrapping_add(1);
+                let start_mark = format!("{}-start-{}", name, tracer.span_count);
+                error::report(mark(&start_mark));
+                start_mark
+            });
+            Span {
+                name,
+                start: tracer.clock.now().ok(),
+                start_mark,
+            }
+        })
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        let start = match self.start {
+            Some(start) => start,
+            None => return,
+        };
+        if let Some(start_mark) = &self.start_mark {
+            error::report(measure(self.name, start_mark));
+        }
+        TRACER.with(|tracer| {
+            let mut tracer = tracer.borrow_mut();
+            if let Ok(end) = tracer.clock.now() {
+                tracer.current.push(TraceEvent {
+                    name: self.name,
+                    start,
+                    duration: end - start,
+                });
+            }
+        });
+    }
+}
+
+fn performance() -> Result<web_sys::Performance, Error> {
+    web_sys::window()
+        .ok_or_else(|| Error::Dom(String::from("Couldn't get window")))?
+        .performance()
+        .ok_or_else(|| Error::Dom(String::from("Couldn't get window.performance")))
+}
+
+fn mark(start_mark: &str) -> Result<(), Error> {
+    performance()?
+        .mark(start_mark)
+        .map_err(Error::js("Couldn't add performance mark"))
+}
+
+/// Measures from the mark of the span, then clears the entries so they don't
+/// pile up in the performance timeline buffer. A devtools recording still shows
+/// the measure, observers and `getEntriesByType` don't.
+fn measure(name: &str, start_mark: &str) -> Result<(), Error> {
+    let performance = performance()?;
+    performance
+        .measure_with_start_mark(name, start_mark)
+        .map_err(Error::js("Couldn't add performance measure"))?;
+    performance.clear_marks_with_mark_name(start_mark);
+    performance.clear_measures_with_measure_name(name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn manual_tracer(max_frames: usize) -> ManualClock {
+        let clock = ManualClock::new(1.);
+        set_clock(clock.clone());
+        set_user_timing(false);
+        set_max_frames(max_frames);
+        set_enabled(true);
+        clear();
+        clock
+    }
+
+    fn event(name: &str, ts: u32, dur: u32, frame: u32) -> String {
+        format!(
+            r#"{{"name":"{}","cat":"frame","ph":"X","ts":{},"dur":{},"pid":1,"tid":1,"args":{{"frame":{}}}}}"#,
+            name, ts, dur, frame
+        )
+    }
+
+    #[test]
+    fn nested_spans_are_complete_events_inside_their_parent() {
+        let clock = manual_tracer(10);
+        {
+            let _update = span!("update");
+            clock.advance(0.5);
+            {
+                let _physics = span!("physics");
+                clock.advance(0.25);
+            }
+            clock.advance(0.125);
+        }
+        end_frame();
+
+        assert_eq!(
+            chrome_trace(),
+            format!(
+                r#"{{"traceEvents":[{},{}],"displayTimeUnit":"ms"}}"#,
+                event("physics", 1_500_000, 250_000, 1),
+                event("update", 1_000_000, 875_000, 1)
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_the_last_frames() {
+        let clock = manual_tracer(2);
+        for name in ["first", "second", "third"] {
+            let _span = Span::begin(name);
+            clock.advance(1.);
+        }
+        end_frame();
+        for name in ["fourth", "fifth"] {
+            {
+                let _span = Span::begin(name);
+                clock.advance(1.);
+            }
+            end_frame();
+        }
+
+        let trace = chrome_trace();
+        assert!(!trace.contains("third"), "{}", trace);
+        assert!(trace.contains(&event("fourth", 4_000_000, 1_000_000, 2)));
+        assert!(trace.contains(&event("fifth", 5_000_000, 1_000_000, 3)));
+
+        set_max_frames(1);
+        let trace = chrome_trace();
+        assert!(!trace.contains("fourth"), "{}", trace);
+        assert!(trace.contains(&event("fifth", 5_000_000, 1_000_000, 3)));
+    }
+}